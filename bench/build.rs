@@ -1,23 +1,48 @@
 use std::process::Command;
 
-const FILE_PATH: &'static str = "examples";
-const BUILD_DIR: &'static str = "build";
-const C_FILES: [&'static str; 2] = ["simple", "mm"];
-const RUST_FILES: [&'static str; 1] = ["simple_rust"];
-const CC: &'static str = "gcc";
-const RUSTC: &'static str = "rustc";
+const FILE_PATH: &str = "examples";
+const HELPERS_PATH: &str = "helpers";
+const BUILD_DIR: &str = "build";
+const C_FILES: [&str; 8] = [
+    "simple",
+    "mm",
+    "missing_run",
+    "run_only",
+    "abi_99",
+    "run_result",
+    "run_count",
+    "threads",
+];
+const C_EXECUTABLES: [&str; 2] = ["markers", "pointer_chase"];
+const RUST_FILES: [&str; 1] = ["simple_rust"];
+const CC: &str = "gcc";
+const RUSTC: &str = "rustc";
 
 fn main() {
     println!("cargo:rerun-if-changed={}", FILE_PATH);
+    println!("cargo:rerun-if-changed={}", HELPERS_PATH);
 
     // Compile C files
     for file in &C_FILES {
         let c_file = file_path(file, "c");
         let out = output_path(file);
         let ret = Command::new(CC)
-            .args(&["-shared", "-O3", "-fPIC", &c_file, "-o", &out])
+            .args(["-shared", "-O3", "-fPIC", "-pthread", &c_file, "-o", &out])
             .status()
-            .expect(&format!("Failed to build {}.c", file));
+            .unwrap_or_else(|_| panic!("Failed to build {}.c", file));
+        if !ret.success() {
+            panic(file, "c");
+        }
+    }
+
+    // Compile C executables
+    for file in &C_EXECUTABLES {
+        let c_file = file_path(file, "c");
+        let out = format!("./{}/{}", BUILD_DIR, file);
+        let ret = Command::new(CC)
+            .args(["-O3", &c_file, "-o", &out])
+            .status()
+            .unwrap_or_else(|_| panic!("Failed to build {}.c", file));
         if !ret.success() {
             panic(file, "c");
         }
@@ -28,9 +53,9 @@ fn main() {
         let rust_file = file_path(file, "rs");
         let out = output_path(file);
         let ret = Command::new(RUSTC)
-            .args(&["--crate-type=dylib", "-O", &rust_file, "-o", &out])
+            .args(["--crate-type=dylib", "-O", &rust_file, "-o", &out])
             .status()
-            .expect(&format!("Failed to build {}.rs", file));
+            .unwrap_or_else(|_| panic!("Failed to build {}.rs", file));
         if !ret.success() {
             panic(file, "rs");
         }
@@ -0,0 +1,8 @@
+/* ABI 99
+ *
+ * A library built for a future version of the bench interface, that bench must refuse to run.
+ **/
+
+unsigned int bench_abi_version() { return 99; }
+
+void run() {}
@@ -0,0 +1,29 @@
+/* Markers
+ *
+ * An executable with a slow setup and teardown, only the loop in between is marked for
+ * measurement.
+ **/
+
+#include <stdio.h>
+#include <unistd.h>
+
+#include "../helpers/bench_marker.h"
+
+int NB_ITERATIONS = 1000000;
+
+int main() {
+  // Setup
+  usleep(300000);
+
+  bench_marker();
+  unsigned long u = 0;
+  for (int i = 0; i < NB_ITERATIONS; i++) {
+    u = (u + i) % NB_ITERATIONS;
+  }
+  bench_marker();
+  printf("u = %lu\n", u);
+
+  // Teardown
+  usleep(300000);
+  return 0;
+}
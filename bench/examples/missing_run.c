@@ -0,0 +1,7 @@
+/* Missing Run
+ *
+ * A library that does not implement the benchmark interface, as it lacks the `run` function.
+ **/
+
+void init(int argc, char *argv[]) {}
+void cleanup() {}
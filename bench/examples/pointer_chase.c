@@ -0,0 +1,39 @@
+/* Pointer chase
+ *
+ * An executable following a random cycle through a large array, most steps land on a different
+ * page and miss in the data TLB.
+ **/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#define NB_ELEMENTS (1 << 22)
+#define NB_STEPS 1000000
+
+int main() {
+  size_t *next = malloc(NB_ELEMENTS * sizeof(size_t));
+  if (next == NULL) {
+    return 1;
+  }
+
+  // Sattolo's algorithm: a random permutation made of a single cycle
+  for (size_t i = 0; i < NB_ELEMENTS; i++) {
+    next[i] = i;
+  }
+  unsigned long seed = 42;
+  for (size_t i = NB_ELEMENTS - 1; i > 0; i--) {
+    seed = seed * 6364136223846793005UL + 1442695040888963407UL;
+    size_t j = (seed >> 33) % i;
+    size_t tmp = next[i];
+    next[i] = next[j];
+    next[j] = tmp;
+  }
+
+  size_t current = 0;
+  for (int step = 0; step < NB_STEPS; step++) {
+    current = next[current];
+  }
+  printf("current = %zu\n", current);
+  free(next);
+  return 0;
+}
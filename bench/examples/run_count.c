@@ -0,0 +1,10 @@
+/* Run Count
+ *
+ * A benchmark counting how many times it has been run since its initialization.
+ **/
+
+int runs = 0;
+
+void init(int argc, char *argv[]) { runs = 0; }
+
+double run_result() { return ++runs; }
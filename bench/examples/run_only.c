@@ -0,0 +1,12 @@
+/* Run Only
+ *
+ * A minimal benchmark that only implements `run`, `init` and `cleanup` are optional.
+ **/
+
+long result = 0;
+
+void run() {
+  for (long i = 0; i < 1000000; i++) {
+    result += i;
+  }
+}
@@ -0,0 +1,6 @@
+/* Run Result
+ *
+ * A benchmark reporting a metric to bench, by implementing `run_result` instead of `run`.
+ **/
+
+double run_result() { return 42.0; }
@@ -0,0 +1,35 @@
+/* Threads
+ *
+ * A benchmark spawning 4 threads, each one busy for about 200ms.
+ **/
+
+#include <pthread.h>
+#include <time.h>
+
+#define NB_THREADS 4
+
+static double elapsed_since(struct timespec *start) {
+  struct timespec now;
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  return (now.tv_sec - start->tv_sec) + (now.tv_nsec - start->tv_nsec) / 1e9;
+}
+
+static void *spin(void *arg) {
+  struct timespec start;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  volatile unsigned long u = 0;
+  while (elapsed_since(&start) < 0.2) {
+    u++;
+  }
+  return NULL;
+}
+
+void run() {
+  pthread_t threads[NB_THREADS];
+  for (int i = 0; i < NB_THREADS; i++) {
+    pthread_create(&threads[i], NULL, spin, NULL);
+  }
+  for (int i = 0; i < NB_THREADS; i++) {
+    pthread_join(threads[i], NULL);
+  }
+}
@@ -0,0 +1,22 @@
+/* Bench Markers
+ *
+ * Delimit the region measured by `bench --marker-fifo PATH`: call `bench_marker()` once at the
+ * start of the region and once at its end. The markers are no-ops when the program is not run by
+ * bench.
+ **/
+
+#include <fcntl.h>
+#include <stdlib.h>
+#include <unistd.h>
+
+static void bench_marker() {
+  static int fifo = -2;
+  if (fifo == -2) {
+    const char *path = getenv("BENCH_MARKER_FIFO");
+    fifo = path ? open(path, O_WRONLY) : -1;
+  }
+  if (fifo >= 0) {
+    char marker = 'm';
+    write(fifo, &marker, 1);
+  }
+}
@@ -0,0 +1,23 @@
+//! Bench Markers
+//!
+//! Delimit the region measured by `bench --marker-fifo PATH`: call `bench_marker()` once at the
+//! start of the region and once at its end. The markers are no-ops when the program is not run by
+//! bench.
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+static FIFO: Mutex<Option<Option<File>>> = Mutex::new(None);
+
+pub fn bench_marker() {
+    let mut fifo = FIFO.lock().unwrap();
+    let fifo = fifo.get_or_insert_with(|| {
+        let path = env::var_os("BENCH_MARKER_FIFO")?;
+        OpenOptions::new().write(true).open(path).ok()
+    });
+    if let Some(fifo) = fifo {
+        fifo.write_all(b"m").ok();
+    }
+}
@@ -1,7 +1,8 @@
 //! The CLI interface of bench
 
+use crate::raw_event::RawEvent;
+use crate::sweep::Parameter;
 pub use clap::Clap;
-use shellwords;
 use std::ffi::{CString, OsString};
 use std::os::raw::{c_char, c_int};
 
@@ -26,6 +27,14 @@ use std::os::raw::{c_char, c_int};
 /// `init` will be called first before starting performance counters and timer,
 /// then `run` is called and its performance is measured, finally the statistics
 /// are collected and `cleanup` is called before printing results and exiting.
+/// Only `run` is mandatory, `init` and `cleanup` can be omitted. A program can
+/// also report a metric (e.g. its throughput) by implementing
+/// `double run_result()` instead of `run`.
+///
+/// The library can also export `unsigned int bench_abi_version()`, returning the
+/// version of the interface it implements. Bench refuses to run libraries built
+/// for another version, libraries without this function are assumed to
+/// implement version 0, the current one.
 /// A Rust interface is also available with the `-r` or `--rust` flag, onlu the
 /// `init` function signature is changed for:
 ///
@@ -33,13 +42,27 @@ use std::os::raw::{c_char, c_int};
 #[derive(Clap)]
 pub struct Args {
     /// Path to the program to benchmark.
-    pub path: String,
+    ///
+    /// Several programs can be given, they are all run with the same arguments and their results
+    /// are reported in a single table.
+    #[clap(required_unless_present_any = &["suite", "config", "pid"])]
+    pub paths: Vec<String>,
+
+    /// Path to a TOML file defining the benchmark: the program `path`, its `args` and `env`, the
+    /// number of `runs` and `warmup` runs and the list of `counters`.
+    ///
+    /// The flags given on the command line take precedence over the config.
+    #[clap(long)]
+    pub config: Option<String>,
 
     /// Arguments passed down to the program.
     #[clap(short, long, default_value = " ")]
     pub args: String,
 
-    /// Verbose output.
+    /// Verbose output, printed on the standard error.
+    ///
+    /// In executable mode, the output of the program is echoed line by line as it runs, prefixed
+    /// with `[NAME]` (or `[NAME:err]` for the standard error).
     #[clap(short, long)]
     pub verbose: bool,
 
@@ -54,6 +77,219 @@ pub struct Args {
     /// Print results as JSON
     #[clap(short, long)]
     pub json: bool,
+
+    /// Print results as a markdown table
+    #[clap(long, conflicts_with = "json")]
+    pub markdown: bool,
+
+    /// Print the individual runs as whitespace-separated columns, for plotting with gnuplot
+    #[clap(long, conflicts_with_all = &["json", "markdown", "compare", "suite"])]
+    pub gnuplot: bool,
+
+    /// Include information about the host system (CPU model, cores and frequency) in the results
+    #[clap(long)]
+    pub sysinfo: bool,
+
+    /// Comma-separated list of counters to collect, or `all`.
+    ///
+    /// Available counters: cycles, ref-cycles, instructions, stalled-cycles-frontend,
+    /// stalled-cycles-backend, task-clock, cache-misses, cache-references, page-faults,
+    /// context-switches, dTLB-load-misses and iTLB-load-misses. Collecting fewer counters avoids
+    /// multiplexing on machines with few hardware counters.
+    #[clap(long, default_value = "all")]
+    pub counters: String,
+
+    /// Collect a raw perf event, as `[LABEL=]TYPE:CONFIG` with hexadecimal type and config.
+    ///
+    /// The event is reported under LABEL, which defaults to `TYPE:CONFIG`. Can be used multiple
+    /// times.
+    #[clap(long = "raw-event", number_of_values = 1)]
+    pub raw_events: Vec<RawEvent>,
+
+    /// Break the cycles, instructions and task clock down per core in the JSON results, to spot
+    /// load imbalance.
+    #[clap(long)]
+    pub per_core: bool,
+
+    /// Sample the counters every given number of milliseconds, and report the counts of each
+    /// interval as a time series in the JSON results.
+    ///
+    /// Raw events and the per-core breakdown are not sampled. With several runs, the time series
+    /// of the last run is reported.
+    #[clap(long)]
+    pub sample_ms: Option<u64>,
+
+    /// Print the p50, p95 and p99 of the execution time and cycles across runs instead of the
+    /// averages.
+    ///
+    /// The percentiles are always included in the JSON results when there are several runs.
+    #[clap(long, conflicts_with_all = &["json", "markdown", "gnuplot", "compare", "suite", "sweep"])]
+    pub percentiles: bool,
+
+    /// Also render the results as a self-contained HTML page with a chart of the execution times,
+    /// written to the given path.
+    #[clap(long, conflicts_with = "gnuplot")]
+    pub html: Option<String>,
+
+    /// Report the number of threads used by the program and its parallel efficiency (shared
+    /// library mode only).
+    ///
+    /// The threads are listed every millisecond from a background thread, which slightly
+    /// increases the counts.
+    #[clap(long)]
+    pub count_threads: bool,
+
+    /// Number of threads used by the program, to compute its parallel efficiency.
+    ///
+    /// Takes precedence over the number of threads observed with `--count-threads`.
+    #[clap(long)]
+    pub threads: Option<u64>,
+
+    /// Write the results to a file instead of the standard output.
+    #[clap(short, long)]
+    pub output: Option<String>,
+
+    /// Path to a second program to benchmark, results are compared against the first program.
+    #[clap(long)]
+    pub compare: Option<String>,
+
+    /// Path to the JSON results of a previous run, exit with code 1 if the cycles or instructions
+    /// count regressed by more than `threshold` percent.
+    ///
+    /// If the program itself failed, bench exits with the program exit code instead (or 70 if it
+    /// was killed), whether there is a regression or not.
+    #[clap(long)]
+    pub baseline: Option<String>,
+
+    /// Path to a file listing several programs to benchmark, one per line as `PATH [ARGS...]`.
+    ///
+    /// Empty lines and lines starting with `#` are ignored. The results of each program are
+    /// reported along with the geometric mean of their execution times.
+    #[clap(long, conflicts_with_all = &["paths", "compare", "baseline"])]
+    pub suite: Option<String>,
+
+    /// Benchmark the program for several values of a parameter, as `NAME=VALUE,VALUE...`.
+    ///
+    /// Each `{NAME}` in the arguments of the program is replaced by the values in turn, the
+    /// execution times are reported along with the speedup relative to the first value.
+    #[clap(long, conflicts_with_all = &["suite", "compare", "baseline", "gnuplot"])]
+    pub sweep: Option<Parameter>,
+
+    /// Measure the already running process with the given PID for `duration` seconds, instead of
+    /// running a program.
+    ///
+    /// The counters follow all the threads of the process, as well as the threads and processes it
+    /// spawns while measured. Observing a process owned by another user usually requires elevated
+    /// privileges.
+    #[clap(
+        long,
+        requires = "duration",
+        conflicts_with_all = &[
+            "paths", "config", "suite", "sweep", "compare", "baseline", "c", "rust", "gnuplot",
+            "percentiles", "raw-events", "per-core"
+        ]
+    )]
+    pub pid: Option<u32>,
+
+    /// Duration of the measurement with `--pid`, in seconds.
+    #[clap(long, requires = "pid")]
+    pub duration: Option<f64>,
+
+    /// Tolerated regression compared to the baseline, in percent.
+    #[clap(long, default_value = "5")]
+    pub threshold: f64,
+
+    /// Set an environment variable for the program, as `KEY=VALUE` (executable mode only).
+    ///
+    /// Can be used multiple times.
+    #[clap(short, long, number_of_values = 1, parse(try_from_str = parse_env))]
+    pub env: Vec<(String, String)>,
+
+    /// Working directory of the program (executable mode only).
+    #[clap(long)]
+    pub cwd: Option<String>,
+
+    /// Feed the content of a file to the program standard input (executable mode only).
+    #[clap(long)]
+    pub stdin: Option<String>,
+
+    /// Kill the program if it runs for more than the given number of seconds (executable mode
+    /// only).
+    #[clap(long)]
+    pub timeout: Option<f64>,
+
+    /// Only measure the region delimited by markers written to the FIFO at the given path, which
+    /// is created if needed (executable mode only).
+    ///
+    /// The program finds the path in the `BENCH_MARKER_FIFO` environment variable, and writes a
+    /// byte to the FIFO at the start and at the end of the region. Helpers for C and Rust programs
+    /// are available in the `helpers` directory.
+    #[clap(long)]
+    pub marker_fifo: Option<String>,
+
+    /// Pin the program to the given core (executable mode only).
+    #[clap(long)]
+    pub pin: Option<usize>,
+
+    /// Run the program with the given niceness, from -20 (highest priority) to 19 (executable mode
+    /// only).
+    ///
+    /// Negative values require privileges (e.g. root or CAP_SYS_NICE).
+    #[clap(long, allow_hyphen_values = true)]
+    pub nice: Option<i32>,
+
+    /// Discard the output of the program (executable mode only).
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// Include the standard output of the program in the JSON results, instead of printing it
+    /// (executable mode only).
+    ///
+    /// The output of the last run is reported, the standard error is still printed.
+    #[clap(long)]
+    pub capture: bool,
+
+    /// Number of times the program is run, the reported counts are averaged over all runs.
+    #[clap(long, default_value = "1")]
+    pub runs: usize,
+
+    /// Number of runs executed before the measurement and discarded, e.g. to warm up the caches.
+    #[clap(long, default_value = "0")]
+    pub warmup: usize,
+
+    /// Run the program until the measurement is stable, instead of a fixed number of runs.
+    ///
+    /// The measurement is considered stable once the coefficient of variation of the cycles (or
+    /// of the wall clock time if cycles are not collected) over the last 5 runs is below
+    /// `cv-threshold`.
+    #[clap(long)]
+    pub auto: bool,
+
+    /// Maximum number of runs with `--auto`.
+    #[clap(long, default_value = "50")]
+    pub max_runs: usize,
+
+    /// Coefficient of variation under which the measurement is considered stable with `--auto`,
+    /// in percent.
+    #[clap(long, default_value = "1")]
+    pub cv_threshold: f64,
+
+    /// Discard the given percentage of fastest and slowest runs before averaging.
+    ///
+    /// At least one run is always kept.
+    #[clap(long, default_value = "0")]
+    pub trim: f64,
+}
+
+/// Parse a `KEY=VALUE` environment variable definition.
+fn parse_env(definition: &str) -> Result<(String, String), String> {
+    match definition.find('=') {
+        Some(idx) if idx > 0 => Ok((
+            definition[..idx].to_string(),
+            definition[idx + 1..].to_string(),
+        )),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", definition)),
+    }
 }
 
 // ——————————————————————————— Host Programs CLI ———————————————————————————— //
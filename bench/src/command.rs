@@ -2,67 +2,660 @@
 //!
 //! This modules handle spawning child processes from a shell command while ensuring that those
 //! processes are correctly terminated on exit.
+//!
+//! Each child is started in its own process group, so that the processes it spawns are terminated
+//! along with it.
 
 use lazy_static::lazy_static;
-use nix::sys::signal::{kill, Signal};
-use nix::unistd::Pid;
-use shellwords;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+#[cfg(target_os = "linux")]
+use nix::sched::{sched_setaffinity, CpuSet};
+use nix::sys::signal::{kill, killpg, Signal};
+use nix::sys::stat::Mode;
+use nix::unistd::{mkfifo, setpgid, Pid};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::process::{Child, Command, ExitStatus};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 lazy_static! {
     static ref PROCESSES: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
 }
 
-/// A wrapper around std::process::Child that is killed when dropped.
+/// Interval between two checks of the child status when waiting with a timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time given to the childs to exit after a SIGTERM, before they are killed with SIGKILL.
+const GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// A wrapper around std::process::Child whose process group is killed when dropped.
 ///
 /// This is especially usefull for ensuring that the child process is killed when the main process
 /// receive a SIGKILL or equivalent.
 pub struct Subprocess {
     process: Child,
+    /// Threads reading the standard output and error, if they are captured.
+    readers: Option<(JoinHandle<String>, JoinHandle<String>)>,
+    /// The exit status and the resource usage of the child, once it is reaped.
+    reaped: Option<(ExitStatus, libc::rusage)>,
+}
+
+/// The captured output of a child process.
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// How a child process ended, reported in the JSON results so that a crash can be told apart
+/// from a slow run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunStatus {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// The signal that terminated the child, if any.
+    pub signal: Option<i32>,
+    /// Whether the child was killed after a timeout.
+    pub timed_out: bool,
+}
+
+impl RunStatus {
+    /// Build the status from the result of `Subprocess::wait_timeout`, `None` meaning that the
+    /// child timed out.
+    pub fn new(status: Option<ExitStatus>) -> Self {
+        match status {
+            Some(status) => Self {
+                success: status.success(),
+                exit_code: status.code(),
+                signal: status.signal(),
+                timed_out: false,
+            },
+            None => Self {
+                success: false,
+                exit_code: None,
+                signal: None,
+                timed_out: true,
+            },
+        }
+    }
 }
 
 impl Subprocess {
     pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
-        let pid = self.process.id();
-        let result = self.process.wait();
-        let mut process = PROCESSES.lock().unwrap();
-        process.remove(&(pid as i32));
-        result
+        loop {
+            if let Some(status) = self.reap(0)? {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Return the exit status of the child if it exited, without blocking.
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        self.reap(libc::WNOHANG)
+    }
+
+    /// Wait for the child to exit for at most `timeout`.
+    ///
+    /// If the child is still running after the deadline it is killed and `None` is returned.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                self.kill();
+                self.wait()?;
+                return Ok(None);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Return the captured output of the child, once it exited.
+    ///
+    /// Return `None` if the output is not captured, or if it has already been returned.
+    pub fn output(&mut self) -> Option<Output> {
+        let (stdout, stderr) = self.readers.take()?;
+        Some(Output {
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+
+    /// Peak resident set size of the child, in kilobytes, once it is reaped.
+    ///
+    /// Unlike `getrusage(RUSAGE_CHILDREN)`, which reports the largest peak among all the childs
+    /// waited for so far, this is the peak of this child (and of its descendants).
+    pub fn peak_rss_kb(&self) -> Option<u64> {
+        self.reaped.map(|(_, usage)| usage.ru_maxrss as u64)
+    }
+
+    /// Reap the child with `wait4`, to collect its own resource usage along with its exit status.
+    ///
+    /// Return `None` if the child is still running and `WNOHANG` is given in `options`.
+    fn reap(&mut self, options: libc::c_int) -> std::io::Result<Option<ExitStatus>> {
+        if let Some((status, _)) = self.reaped {
+            return Ok(Some(status));
+        }
+        let mut status = 0;
+        let mut usage = MaybeUninit::<libc::rusage>::uninit();
+        // SAFETY: the pointers are valid, and `wait4` initializes `usage` when it returns the PID
+        // of the child.
+        let pid = unsafe {
+            libc::wait4(
+                self.process.id() as libc::pid_t,
+                &mut status,
+                options,
+                usage.as_mut_ptr(),
+            )
+        };
+        match pid {
+            0 => Ok(None),
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    Ok(None)
+                } else {
+                    Err(err)
+                }
+            }
+            _ => {
+                let status = ExitStatus::from_raw(status);
+                // SAFETY: `wait4` returned the PID of the child.
+                self.reaped = Some((status, unsafe { usage.assume_init() }));
+                self.unregister();
+                Ok(Some(status))
+            }
+        }
+    }
+
+    /// Kill the process group of the child.
+    fn kill(&self) {
+        // The group might be empty already, in which case there is nothing to do.
+        killpg(self.pgid(), Signal::SIGKILL).ok();
+    }
+
+    /// The child is the leader of its own process group.
+    fn pgid(&self) -> Pid {
+        Pid::from_raw(self.process.id() as i32)
+    }
+
+    /// Remove the child from the list of running processes, must be called once it is reaped.
+    fn unregister(&self) {
+        let mut processes = PROCESSES.lock().unwrap();
+        processes.remove(&(self.process.id() as i32));
     }
 }
 
 impl Drop for Subprocess {
     fn drop(&mut self) {
-        self.process.kill().ok();
+        self.kill();
     }
 }
 
 pub struct Process {
     process: Command,
+    capture: bool,
+    /// Prefix of the lines echoed while the child runs, if its output is streamed.
+    stream: Option<String>,
+    /// Core the child is pinned to, if any.
+    core: Option<usize>,
+    /// Niceness of the child, if it differs from ours.
+    nice: Option<i32>,
 }
 
 impl Process {
     pub fn new(path: &str, args: &str) -> Self {
         let mut cmd = Command::new(path);
         cmd.args(shellwords::split(args).unwrap());
-        Process { process: cmd }
+        Process {
+            process: cmd,
+            capture: false,
+            stream: None,
+            core: None,
+            nice: None,
+        }
+    }
+
+    /// Set environment variables for the child process, in addition to the inherited ones.
+    pub fn envs<'a, I>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = &'a (String, String)>,
+    {
+        for (key, value) in vars {
+            self.process.env(key, value);
+        }
+        self
+    }
+
+    /// Set the working directory of the child process.
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.process.current_dir(dir);
+        self
+    }
+
+    /// Use the given file as the standard input of the child process.
+    ///
+    /// The file is owned by the command, and is closed once the `Process` is dropped.
+    pub fn stdin(&mut self, file: File) -> &mut Self {
+        self.process.stdin(Stdio::from(file));
+        self
+    }
+
+    /// Capture the standard output and error of the child process, instead of inheriting them.
+    ///
+    /// The output is available through `Subprocess::output` once the child exited.
+    pub fn capture_output(&mut self) -> &mut Self {
+        self.process.stdout(Stdio::piped()).stderr(Stdio::piped());
+        self.capture = true;
+        self
+    }
+
+    /// Echo the standard output and error of the child process line by line while it runs,
+    /// prefixed with `[prefix]` and `[prefix:err]` respectively.
+    ///
+    /// The lines are echoed on our standard error, so that they never mix with the results. The
+    /// output is also available through `Subprocess::output` once the child exited.
+    pub fn stream_output(&mut self, prefix: &str) -> &mut Self {
+        self.process.stdout(Stdio::piped()).stderr(Stdio::piped());
+        self.stream = Some(prefix.to_string());
+        self
+    }
+
+    /// Restrict the child process to the given core.
+    pub fn pin(&mut self, core: usize) -> &mut Self {
+        self.core = Some(core);
+        self
+    }
+
+    /// Set the niceness of the child process, negative values (higher priorities) usually require
+    /// privileges.
+    pub fn nice(&mut self, nice: i32) -> &mut Self {
+        self.nice = Some(nice);
+        self
     }
 
     pub fn spawn(&mut self) -> std::io::Result<Subprocess> {
-        self.process.spawn().map(|process| {
+        let (core, nice) = (self.core, self.nice);
+        // SAFETY: `setpgid`, `sched_setaffinity` and `setpriority` are async-signal-safe, and the
+        // CPU set lives on the stack.
+        unsafe {
+            self.process.pre_exec(move || {
+                let os_error = |_| std::io::Error::last_os_error();
+                setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(os_error)?;
+                if let Some(core) = core {
+                    pin_to_core(core)?;
+                }
+                if let Some(nice) = nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let capture = self.capture;
+        let stream = self.stream.clone();
+        self.process.spawn().map(|mut process| {
             let pid = process.id();
             let mut processes = PROCESSES.lock().unwrap();
             processes.insert(pid as i32);
-            Subprocess { process }})
+            // The pipes are drained while the child runs, so that it never blocks on a full pipe.
+            let readers = match stream {
+                Some(prefix) => Some((
+                    read_in_background(process.stdout.take(), Some(format!("[{}]", prefix))),
+                    read_in_background(process.stderr.take(), Some(format!("[{}:err]", prefix))),
+                )),
+                None if capture => Some((
+                    read_in_background(process.stdout.take(), None),
+                    read_in_background(process.stderr.take(), None),
+                )),
+                None => None,
+            };
+            Subprocess {
+                process,
+                readers,
+                reaped: None,
+            }
+        })
     }
 }
 
+/// Read a pipe until it is closed from another thread, invalid UTF-8 is replaced.
+///
+/// If a prefix is given, each line is also echoed on the standard error as soon as it is read.
+fn read_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    prefix: Option<String>,
+) -> JoinHandle<String> {
+    thread::spawn(move || match (pipe, prefix) {
+        (Some(pipe), Some(prefix)) => echo_lines(pipe, &prefix, io::stderr()),
+        (Some(mut pipe), None) => {
+            let mut buffer = Vec::new();
+            pipe.read_to_end(&mut buffer).ok();
+            String::from_utf8_lossy(&buffer).into_owned()
+        }
+        (None, _) => String::new(),
+    })
+}
+
+/// Read `pipe` line by line until it is closed, writing each line to `out` after `prefix`.
+///
+/// Return everything that has been read, invalid UTF-8 is replaced.
+fn echo_lines<R: Read, W: Write>(pipe: R, prefix: &str, mut out: W) -> String {
+    let mut pipe = BufReader::new(pipe);
+    let mut content = String::new();
+    let mut line = Vec::new();
+    while matches!(pipe.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+        let text = String::from_utf8_lossy(&line);
+        writeln!(out, "{} {}", prefix, text.trim_end_matches('\n')).ok();
+        content.push_str(&text);
+        line.clear();
+    }
+    content
+}
+
+/// Environment variable holding the path of the marker FIFO, used by the program to emit markers.
+pub const MARKER_FIFO_VAR: &str = "BENCH_MARKER_FIFO";
+
+/// A named pipe on which the program writes a byte at the start and at the end of the region to
+/// measure.
+pub struct MarkerFifo {
+    fifo: File,
+}
+
+impl MarkerFifo {
+    /// Open the FIFO at `path`, it is created if it does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        match mkfifo(path, Mode::S_IRUSR | Mode::S_IWUSR) {
+            Ok(()) | Err(nix::Error::Sys(Errno::EEXIST)) => (),
+            Err(_) => return Err(io::Error::last_os_error()),
+        }
+        if !fs::metadata(path)?.file_type().is_fifo() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not a FIFO", path.display()),
+            ));
+        }
+        // Opening the write end too never blocks, and the pipe stays open when the program closes
+        // its own end.
+        let fifo = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { fifo })
+    }
+
+    /// Wait for the next marker.
+    ///
+    /// Return false if the child exited, or if the deadline passed, before emitting the marker.
+    pub fn wait_marker(
+        &mut self,
+        child: &mut Subprocess,
+        deadline: Option<Instant>,
+    ) -> io::Result<bool> {
+        loop {
+            let mut fds = [PollFd::new(self.fifo.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, POLL_INTERVAL.as_millis() as i32) {
+                Ok(0) | Err(nix::Error::Sys(Errno::EINTR)) => (),
+                Ok(_) => {
+                    let mut marker = [0];
+                    self.fifo.read_exact(&mut marker)?;
+                    return Ok(true);
+                }
+                Err(_) => return Err(io::Error::last_os_error()),
+            }
+            if child.try_wait()?.is_some() {
+                return Ok(false);
+            }
+            if matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Restrict the calling process to the given core.
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> std::io::Result<()> {
+    let mut cpu_set = CpuSet::new();
+    cpu_set
+        .set(core)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid core"))?;
+    sched_setaffinity(Pid::from_raw(0), &cpu_set).map_err(|_| std::io::Error::last_os_error())
+}
+
+/// Pinning is not supported on this platform.
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "pinning to a core is not supported on this platform",
+    ))
+}
+
 /// Kill all runing childs.
 pub fn kill_all_childs() {
     let processes = PROCESSES.lock().unwrap();
-    for process_id in processes.iter() {
-        kill(Pid::from_raw(*process_id), Signal::SIGTERM).expect("Failed to kill child processes");
+    terminate(processes.iter().copied());
+}
+
+/// Ask the process groups led by the given processes to terminate with SIGTERM, and kill them with
+/// SIGKILL once the childs exited or after the grace period.
+fn terminate<I: IntoIterator<Item = i32>>(process_ids: I) {
+    let pids: Vec<Pid> = process_ids.into_iter().map(Pid::from_raw).collect();
+    for pid in &pids {
+        // The child might have exited in the meantime, in which case there is nothing to do.
+        killpg(*pid, Signal::SIGTERM).ok();
+    }
+    let deadline = Instant::now() + GRACE_PERIOD;
+    while pids.iter().any(|&pid| is_alive(pid)) && Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+    }
+    // The descendants of the childs that exited might still be running.
+    for pid in pids {
+        killpg(pid, Signal::SIGKILL).ok();
+    }
+}
+
+/// Return true if the process exists and is not a zombie waiting to be reaped.
+fn is_alive(pid: Pid) -> bool {
+    if kill(pid, None).is_err() {
+        return false;
+    }
+    // The state follows the command name, which is between parentheses and may contain spaces.
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => {
+            let state = stat
+                .rfind(')')
+                .and_then(|idx| stat[idx + 1..].split_whitespace().next());
+            state != Some("Z")
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn env_is_passed_to_child() {
+        let path = std::env::temp_dir().join("bench_test_env.txt");
+        let args = format!("-c 'echo \"$FOO\" > {}'", path.display());
+        let mut cmd = Process::new("sh", &args);
+        cmd.envs(&[(String::from("FOO"), String::from("bar"))]);
+        let status = cmd.spawn().unwrap().wait().unwrap();
+
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\n");
+    }
+
+    #[test]
+    fn stdin_from_file() {
+        let dir = std::env::temp_dir().join("bench_test_stdin");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("input.txt"), "some input\n").unwrap();
+        let mut cmd = Process::new("sh", "-c 'cat > output.txt'");
+        cmd.current_dir(&dir)
+            .stdin(File::open(dir.join("input.txt")).unwrap());
+        let status = cmd.spawn().unwrap().wait().unwrap();
+
+        assert!(status.success());
+        let output = fs::read_to_string(dir.join("output.txt")).unwrap();
+        assert_eq!(output, "some input\n");
+    }
+
+    #[test]
+    fn capture_output() {
+        let mut child = Process::new("echo", "hello")
+            .capture_output()
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+
+        let output = child.output().unwrap();
+        assert_eq!(output.stdout, "hello\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn stream_output() {
+        let mut child = Process::new("sh", "-c 'echo hello; echo oops >&2'")
+            .stream_output("sh")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+
+        // The output is still returned once streamed
+        let output = child.output().unwrap();
+        assert_eq!(output.stdout, "hello\n");
+        assert_eq!(output.stderr, "oops\n");
+
+        let mut echoed = Vec::new();
+        let content = echo_lines("hello\nworld".as_bytes(), "[sh]", &mut echoed);
+        assert_eq!(content, "hello\nworld");
+        assert_eq!(String::from_utf8(echoed).unwrap(), "[sh] hello\n[sh] world\n");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn pin_to_core() {
+        let child = Process::new("sleep", "100").pin(0).spawn().unwrap();
+        let status = fs::read_to_string(format!("/proc/{}/status", child.process.id())).unwrap();
+        let affinity = status
+            .lines()
+            .find(|line| line.starts_with("Cpus_allowed_list:"))
+            .unwrap();
+        assert_eq!(affinity.split_whitespace().nth(1), Some("0"));
+    }
+
+    #[test]
+    fn child_niceness() {
+        let mut child = Process::new("nice", "")
+            .nice(5)
+            .capture_output()
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let niceness: i32 = child.output().unwrap().stdout.trim().parse().unwrap();
+
+        // The niceness is relative to ours, which can not be lowered without privileges
+        assert!(niceness >= 5);
+    }
+
+    #[test]
+    fn timeout_kills_child() {
+        let start = Instant::now();
+        let mut child = Process::new("sleep", "100").spawn().unwrap();
+        let status = child.wait_timeout(Duration::from_secs(1)).unwrap();
+
+        assert!(status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn escalate_to_sigkill() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let ready = std::env::temp_dir().join("bench_test_sigterm_ready");
+        fs::remove_file(&ready).ok();
+        let args = format!("-c 'trap \"\" TERM; touch {}; exec sleep 100'", ready.display());
+        let mut child = Process::new("sh", &args).spawn().unwrap();
+        // Wait for the trap to be installed
+        while !ready.exists() {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let start = Instant::now();
+        terminate(Some(child.process.id() as i32));
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn kill_process_group() {
+        let path = std::env::temp_dir().join("bench_test_grandchild_pid");
+        fs::remove_file(&path).ok();
+        let args = format!("-c 'sleep 100 & echo $! > {}; wait'", path.display());
+        let child = Process::new("sh", &args).spawn().unwrap();
+        let child_pid = Pid::from_raw(child.process.id() as i32);
+        let grandchild_pid = loop {
+            match fs::read_to_string(&path).map(|pid| pid.trim().parse()) {
+                Ok(Ok(pid)) => break Pid::from_raw(pid),
+                _ => thread::sleep(POLL_INTERVAL),
+            }
+        };
+        assert!(is_alive(grandchild_pid));
+
+        drop(child);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (is_alive(child_pid) || is_alive(grandchild_pid)) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!is_alive(child_pid));
+        assert!(!is_alive(grandchild_pid));
+    }
+
+    #[test]
+    fn peak_rss() {
+        // dd allocates a 64 MiB buffer and fills it from /dev/zero.
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=64M count=1")
+            .spawn()
+            .unwrap();
+        assert!(child.peak_rss_kb().is_none());
+        assert!(child.wait().unwrap().success());
+        assert!(child.peak_rss_kb().unwrap() > 60 * 1024);
+
+        // The peak of a small child is not affected by the previous ones
+        let mut child = Process::new("true", "").spawn().unwrap();
+        assert!(child.wait().unwrap().success());
+        assert!(child.peak_rss_kb().unwrap() < 60 * 1024);
+    }
+
+    #[test]
+    fn child_working_directory() {
+        let dir = std::env::temp_dir().join("bench_test_cwd");
+        fs::create_dir_all(&dir).unwrap();
+        let mut cmd = Process::new("sh", "-c 'pwd > cwd.txt'");
+        cmd.current_dir(&dir);
+        let status = cmd.spawn().unwrap().wait().unwrap();
+
+        assert!(status.success());
+        let cwd = fs::read_to_string(dir.join("cwd.txt")).unwrap();
+        assert_eq!(
+            Path::new(cwd.trim()).canonicalize().unwrap(),
+            dir.canonicalize().unwrap()
+        );
     }
 }
@@ -0,0 +1,139 @@
+//! Config
+//!
+//! A benchmark definition read from a TOML file with `--config`, so that it can be checked into a
+//! repository:
+//!
+//! ```toml
+//! path = "build/mm"
+//! args = "512"
+//! runs = 10
+//! warmup = 2
+//! counters = ["cycles", "instructions"]
+//!
+//! [env]
+//! OMP_NUM_THREADS = "4"
+//! ```
+//!
+//! All the fields are optional, the flags given on the command line take precedence.
+use crate::cli::Args;
+use clap::{ArgMatches, FromArgMatches, IntoApp};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    path: Option<String>,
+    args: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    runs: Option<usize>,
+    warmup: Option<usize>,
+    counters: Option<Vec<String>>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Config, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Config::parse(&content)
+    }
+
+    /// Fill the arguments that were not given on the command line, as reported by `matches`.
+    ///
+    /// The environment variables of the config are added to the ones of the command line, which
+    /// take precedence.
+    fn apply(self, args: &mut Args, matches: &ArgMatches) {
+        let is_unset = |name: &str| matches.occurrences_of(name) == 0;
+        if let Some(path) = self.path.filter(|_| args.paths.is_empty()) {
+            args.paths.push(path);
+        }
+        if let Some(program_args) = self.args.filter(|_| is_unset("args")) {
+            args.args = program_args;
+        }
+        for (key, value) in self.env {
+            if !args.env.iter().any(|(cli_key, _)| *cli_key == key) {
+                args.env.push((key, value));
+            }
+        }
+        if let Some(runs) = self.runs.filter(|_| is_unset("runs")) {
+            args.runs = runs;
+        }
+        if let Some(warmup) = self.warmup.filter(|_| is_unset("warmup")) {
+            args.warmup = warmup;
+        }
+        if let Some(counters) = self.counters.filter(|_| is_unset("counters")) {
+            args.counters = counters.join(",");
+        }
+    }
+}
+
+/// Parse the command line, completed by the config file given with `--config`, if any.
+pub fn parse_args<I, T>(argv: I) -> Result<Args, String>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = Args::into_app().get_matches_from(argv);
+    let mut args = Args::from_arg_matches(&matches);
+    if let Some(path) = args.config.clone() {
+        let config =
+            Config::load(&path).map_err(|err| format!("could not load '{}': {}", path, err))?;
+        config.apply(&mut args, &matches);
+        if args.paths.is_empty() && args.suite.is_none() {
+            return Err(format!(
+                "'{}' does not define the program to benchmark",
+                path
+            ));
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            "path = \"build/simple\"\nruns = 3\ncounters = [\"cycles\", \"task-clock\"]\n\n\
+             [env]\nKEY = \"value\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.path.as_deref(), Some("build/simple"));
+        assert_eq!(config.runs, Some(3));
+        assert_eq!(config.env["KEY"], "value");
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn cli_takes_precedence() {
+        let path = std::env::temp_dir().join("bench_test_precedence.toml");
+        fs::write(
+            &path,
+            "path = \"true\"\nruns = 3\nwarmup = 2\n\n[env]\nA = \"config\"\nB = \"config\"\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        let args = parse_args([
+            "bench", "--config", path, "--runs", "5", "--env", "A=cli", "--warmup", "0",
+        ])
+        .unwrap();
+        assert_eq!(args.paths, ["true"]);
+        assert_eq!(args.runs, 5);
+        assert_eq!(args.warmup, 0);
+        assert!(args.env.contains(&("A".to_string(), "cli".to_string())));
+        assert!(!args.env.contains(&("A".to_string(), "config".to_string())));
+        assert!(args.env.contains(&("B".to_string(), "config".to_string())));
+
+        let args = parse_args(["bench", "--config", path, "false"]).unwrap();
+        assert_eq!(args.paths, ["false"]);
+        assert_eq!(args.runs, 3);
+    }
+}
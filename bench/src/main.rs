@@ -2,69 +2,573 @@
 extern crate dlopen_derive;
 mod cli;
 mod command;
+mod config;
 mod monitor;
 mod program;
+mod raw_event;
+mod report;
 mod stats;
+mod suite;
+mod sweep;
+mod sysinfo;
 
-use cli::{Args, Clap};
-use monitor::Monitor;
+use cli::Args;
+use command::RunStatus;
+use monitor::{parse_events, Counters, Event, Monitor, Monitoring, ThreadTracker};
 use program::{CProgram, ExternalProgram, RustProgram};
-use stats::Stats;
+use report::Report;
+use serde::Serialize;
+use stats::{coefficient_of_variation, Comparison, Sample, Stats};
+use suite::{Suite, Target};
+use sweep::Sweep;
+use sysinfo::SystemInfo;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
 use std::process::exit;
+use std::time::{Duration, Instant};
 use ctrlc::set_handler;
 
+/// Number of consecutive runs that must be stable with `--auto`.
+const AUTO_WINDOW: usize = 5;
+
+/// Exit code of bench when the benchmarked program failed without an exit code of its own, i.e.
+/// when it was killed by a signal or after a timeout.
+const TARGET_FAILED: i32 = 70;
+
 fn main() {
-    let args = Args::parse();
+    let args = match config::parse_args(std::env::args_os()) {
+        Ok(args) => args,
+        Err(err) => {
+            println!("Error: {}", err);
+            exit(1);
+        }
+    };
     set_signal_handler();
-    let stats = match (args.c, args.rust) {
-        (false, false) => benchmark_executable(&args.path, &args.args),
-        (true, false) => benchmark_shared::<CProgram>(&args.path, &args.args),
-        (false, true) => benchmark_shared::<RustProgram>(&args.path, &args.args),
-        (true, true) => {
+    let events = match parse_events(&args.counters) {
+        Ok(events) => events,
+        Err(err) => {
+            println!("Error: {}", err);
+            exit(1);
+        }
+    };
+    if args.verbose {
+        let names: Vec<&str> = events.iter().map(|event| event.name()).collect();
+        eprintln!("Collecting counters: {}", names.join(", "));
+    }
+    if args.c && args.rust {
+        println!("Error: flags '-c' and '-r' can't be both used, only one ABI can be selected.");
+        exit(1);
+    }
+    if let Some(cwd) = &args.cwd {
+        if !Path::new(cwd).is_dir() {
+            println!("Error: working directory '{}' does not exist", cwd);
+            exit(1);
+        }
+    }
+    if let Some(timeout) = args.timeout {
+        if !timeout.is_finite() || timeout <= 0. {
+            println!("Error: the timeout must be a positive number of seconds");
+            exit(1);
+        }
+    }
+    if args.runs == 0 || args.max_runs == 0 {
+        println!("Error: the number of runs must be at least 1");
+        exit(1);
+    }
+    if args.sample_ms == Some(0) {
+        println!("Error: the sampling interval must be at least 1 ms");
+        exit(1);
+    }
+    if !(0. ..50.).contains(&args.trim) {
+        println!("Error: the trimmed percentage must be in [0, 50)");
+        exit(1);
+    }
+    let baseline = args.baseline.as_ref().map(|path| match load_baseline(path) {
+        Ok(baseline) => baseline,
+        Err(err) => {
+            println!("Error: could not load baseline '{}': {}", path, err);
+            exit(1);
+        }
+    });
+    if let Some(duration) = args.duration {
+        if !duration.is_finite() || duration <= 0. {
+            println!("Error: the duration must be a positive number of seconds");
+            exit(1);
+        }
+    }
+    if let (Some(pid), Some(duration)) = (args.pid, args.duration) {
+        let stats = match benchmark_pid(&args, pid, Duration::from_secs_f64(duration), &events) {
+            Ok(stats) => stats,
+            Err(err) => {
+                println!("Error: could not attach to process {}: {}", pid, err);
+                exit(1);
+            }
+        };
+        print_output(&args, &format_results(&args, &stats, Stats::markdown));
+        return;
+    }
+    if let Some(suite) = &args.suite {
+        let (output, failure) = benchmark_suite(&args, suite, &events);
+        print_output(&args, &output);
+        if let Some(code) = failure {
+            exit(code);
+        }
+        return;
+    }
+    if args.paths.len() > 1 {
+        if args.compare.is_some() || args.baseline.is_some() || args.sweep.is_some() {
+            println!("Error: '--compare', '--baseline' and '--sweep' expect a single program");
+            exit(1);
+        }
+        let targets = args
+            .paths
+            .iter()
+            .map(|path| Target {
+                path: path.clone(),
+                args: args.args.trim().to_string(),
+            })
+            .collect();
+        let (suite, failure) = benchmark_targets(&args, targets, &events);
+        print_output(&args, &format_results(&args, &suite, Suite::markdown));
+        if let Some(code) = failure {
+            exit(code);
+        }
+        return;
+    }
+    let path = args.paths[0].as_str();
+    if let Some(parameter) = &args.sweep {
+        if !args.args.contains(&parameter.placeholder()) {
             println!(
-                "Error: flags '-c' and '-r' can't be both used, only one ABI can be selected."
+                "Error: the arguments of the program do not contain '{}'",
+                parameter.placeholder()
             );
             exit(1);
         }
+        let (sweep, failure) = benchmark_sweep(&args, path, &events);
+        print_output(&args, &format_results(&args, &sweep, Sweep::markdown));
+        if let Some(code) = failure {
+            exit(code);
+        }
+        return;
+    }
+    let (stats, mut failure) = benchmark(&args, path, &args.args, &events);
+    let regressions = baseline.map(|baseline| baseline.delta(&stats).regressions(args.threshold));
+
+    let output = match &args.compare {
+        Some(other_path) => {
+            let (other, other_failure) = benchmark(&args, other_path, &args.args, &events);
+            failure = failure.or(other_failure);
+            let comparison = Comparison::new(stats, other);
+            format_results(&args, &comparison, Comparison::markdown)
+        }
+        None if args.gnuplot => stats.gnuplot(),
+        None if args.percentiles => stats.percentiles(),
+        None => format_results(&args, &stats, Stats::markdown),
     };
+    print_output(&args, &output);
 
+    // A failure of the program takes precedence over regressions, as the results can not be
+    // trusted in that case. Printed on stderr, so that it does not corrupt the JSON output.
+    match regressions {
+        Some(Some(regressions)) => {
+            for regression in &regressions {
+                eprintln!(
+                    "Regression: {} increased by {:.1}% compared to the baseline (threshold: {}%)",
+                    regression.name, regression.change, args.threshold
+                );
+            }
+            if !regressions.is_empty() && failure.is_none() {
+                failure = Some(1);
+            }
+        }
+        // The check must not pass silently when the counters are not available
+        Some(None) => {
+            eprintln!(
+                "Error: neither the cycles nor the instructions could be compared to the baseline"
+            );
+            failure = failure.or(Some(1));
+        }
+        None => {}
+    }
+    if let Some(code) = failure {
+        exit(code);
+    }
+}
+
+/// Load the JSON results of a previous run.
+fn load_baseline(path: &str) -> Result<Stats, String> {
+    let json = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    Stats::from_json(&json).map_err(|err| err.to_string())
+}
+
+/// Benchmark each program of the suite listed in the file at `suite_path`, and return the
+/// formatted results along with the exit code of the first program that failed, if any.
+fn benchmark_suite(args: &Args, suite_path: &str, events: &[Event]) -> (String, Option<i32>) {
+    let targets = match fs::read_to_string(suite_path)
+        .map_err(|err| err.to_string())
+        .and_then(|content| Target::parse_suite(&content))
+    {
+        Ok(targets) => targets,
+        Err(err) => {
+            println!("Error: could not load suite '{}': {}", suite_path, err);
+            exit(1);
+        }
+    };
+    let (suite, failure) = benchmark_targets(args, targets, events);
+    let output = format_results(args, &suite, Suite::markdown);
+    (output, failure)
+}
+
+/// Benchmark each target, and return the results along with the exit code of the first target
+/// that failed, if any.
+fn benchmark_targets(args: &Args, targets: Vec<Target>, events: &[Event]) -> (Suite, Option<i32>) {
+    let mut failure = None;
+    let results = targets
+        .into_iter()
+        .map(|target| {
+            let (stats, target_failure) = benchmark(args, &target.path, &target.args, events);
+            failure = failure.or(target_failure);
+            (target, stats)
+        })
+        .collect();
+    (Suite::new(results), failure)
+}
+
+/// Benchmark the program at `path` for each value of the swept parameter, and return the results
+/// along with the exit code of the first run that failed, if any.
+fn benchmark_sweep(args: &Args, path: &str, events: &[Event]) -> (Sweep, Option<i32>) {
+    let parameter = args.sweep.as_ref().unwrap();
+    let mut failure = None;
+    let results = parameter
+        .values
+        .iter()
+        .map(|value| {
+            let program_args = parameter.substitute(&args.args, value);
+            let (stats, run_failure) = benchmark(args, path, &program_args, events);
+            failure = failure.or(run_failure);
+            (value.clone(), stats)
+        })
+        .collect();
+    (Sweep::new(&parameter.name, results), failure)
+}
+
+/// Format the results as selected by the CLI arguments.
+fn format_results<T, F>(args: &Args, results: &T, markdown: F) -> String
+where
+    T: Serialize + fmt::Display,
+    F: FnOnce(&T) -> String,
+{
+    let system = if args.sysinfo {
+        Some(SystemInfo::collect())
+    } else {
+        None
+    };
+    let report = Report::new(results, system);
+    if let Some(path) = &args.html {
+        if let Err(err) = write_output(path, &report.html()) {
+            println!("Error: could not write HTML report to '{}': {}", path, err);
+            exit(1);
+        }
+    }
     if args.json {
-        println!("{}", stats.json());
+        return report.json();
+    }
+    let results = if args.markdown {
+        markdown(results)
     } else {
-        println!("{}", stats)
+        results.to_string()
+    };
+    match report.system() {
+        Some(system) => format!("{}\n\n{}", system, results),
+        None => results,
+    }
+}
+
+/// Print the results, or write them to the output file if one was given.
+fn print_output(args: &Args, output: &str) {
+    match &args.output {
+        Some(path) => {
+            if let Err(err) = write_output(path, output) {
+                println!("Error: could not write results to '{}': {}", path, err);
+                exit(1);
+            }
+        }
+        None => println!("{}", output),
     }
 }
 
-fn benchmark_executable(path: &str, args: &str) -> Stats {
-    // Prepare monitor & command
-    let mut monitor = Monitor::new();
-    let mut cmd = command::Process::new(path, args);
+/// Benchmark the program at `path` with the arguments `program_args`, using the mode selected by
+/// the CLI arguments.
+///
+/// Also return the exit code of the first failed run, if any.
+fn benchmark(
+    args: &Args,
+    path: &str,
+    program_args: &str,
+    events: &[Event],
+) -> (Stats, Option<i32>) {
+    let mut monitor = Monitor::new(events).raw_events(&args.raw_events);
+    if args.per_core {
+        monitor = monitor.per_core();
+    }
+    if let Some(sample_ms) = args.sample_ms {
+        monitor = monitor.sample_every(Duration::from_millis(sample_ms));
+    }
+    let (stats, failure) = if args.c {
+        let stats = benchmark_shared::<CProgram>(path, program_args, args, &mut monitor);
+        (stats, None)
+    } else if args.rust {
+        let stats = benchmark_shared::<RustProgram>(path, program_args, args, &mut monitor);
+        (stats, None)
+    } else {
+        let mut failure = None;
+        let mut status: Option<RunStatus> = None;
+        let mut stdout = None;
+        let stats = measure(args, path, || {
+            let (counters, run_status, output) =
+                benchmark_executable(path, program_args, args, &mut monitor);
+            failure = failure.or_else(|| failure_code(&run_status));
+            if status.is_none_or(|status| status.success) {
+                status = Some(run_status);
+            }
+            if args.capture {
+                stdout = output.map(|output| output.stdout);
+            }
+            counters
+        });
+        (stats.with_stdout(stdout).with_status(status), failure)
+    };
+    let stats = stats.with_threads(args.threads);
 
-    // Run & collect stats
+    // Printed on stderr, so that it does not corrupt the JSON output
+    if let Some(warning) = stats.frequency_warning() {
+        eprintln!("Warning: while benchmarking {}, {}", path, warning);
+    }
+    if let Some(warning) = stats.parallel_efficiency_warning() {
+        eprintln!("Warning: while benchmarking {}, {}", path, warning);
+    }
+    (stats, failure)
+}
+
+/// Measure the running process `pid` for `duration`, without waiting for it to terminate.
+fn benchmark_pid(args: &Args, pid: u32, duration: Duration, events: &[Event]) -> io::Result<Stats> {
+    let mut monitor = Monitor::attach(events, pid)?;
+    if let Some(sample_ms) = args.sample_ms {
+        monitor = monitor.sample_every(Duration::from_millis(sample_ms));
+    }
     monitor.start();
+    std::thread::sleep(duration);
+    let stats = Stats::new(monitor.stop()).with_threads(args.threads);
+    if let Some(warning) = stats.parallel_efficiency_warning() {
+        eprintln!("Warning: while measuring process {}, {}", pid, warning);
+    }
+    Ok(stats)
+}
+
+/// Call `run` as many times as selected by the CLI arguments, and aggregate the counters.
+fn measure<F>(args: &Args, path: &str, mut run: F) -> Stats
+where
+    F: FnMut() -> Counters,
+{
+    for _ in 0..args.warmup {
+        run();
+    }
+    let mut runs = if args.auto {
+        run_until_stable(run, args.max_runs, args.cv_threshold)
+    } else {
+        (0..args.runs).map(|_| run()).collect()
+    };
+    if args.verbose && args.auto {
+        eprintln!("Measurement of {} stable after {} runs", path, runs.len());
+    }
+    let samples = runs.iter().map(Sample::new).collect();
+    let total_runs = runs.len();
+    let time_series = runs
+        .last_mut()
+        .map(|last| std::mem::take(&mut last.time_series))
+        .unwrap_or_default();
+    stats::trim(&mut runs, args.trim);
+    Stats::new(stats::mean(&runs))
+        .with_runs(total_runs)
+        .with_samples(samples)
+        .with_time_series(time_series)
+}
+
+/// Run until the coefficient of variation of the last `AUTO_WINDOW` runs is below `threshold`
+/// percent, or until `max_runs` is reached.
+///
+/// The variation is measured on cycles, or on the wall clock time if cycles are not collected.
+fn run_until_stable<F>(mut run: F, max_runs: usize, threshold: f64) -> Vec<Counters>
+where
+    F: FnMut() -> Counters,
+{
+    let mut runs: Vec<Counters> = Vec::new();
+    while runs.len() < max_runs {
+        runs.push(run());
+        if runs.len() < AUTO_WINDOW {
+            continue;
+        }
+        let window: Vec<f64> = runs[runs.len() - AUTO_WINDOW..]
+            .iter()
+            .map(|counters| counters.cycles.unwrap_or(counters.wall_clock) as f64)
+            .collect();
+        match coefficient_of_variation(&window) {
+            Some(cv) if cv * 100. <= threshold => break,
+            _ => (),
+        }
+    }
+    runs
+}
+
+/// Write the results to the file at `path`, creating the parent directories if needed.
+fn write_output<P: AsRef<Path>>(path: P, output: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", output))
+}
+
+/// Return the exit code bench should use if the program failed.
+fn failure_code(status: &RunStatus) -> Option<i32> {
+    if status.success {
+        None
+    } else {
+        Some(status.exit_code.unwrap_or(TARGET_FAILED))
+    }
+}
+
+/// Run the program once, and return the counters along with how the program ended and the
+/// captured output, if any.
+fn benchmark_executable(
+    path: &str,
+    program_args: &str,
+    args: &Args,
+    monitor: &mut Monitor,
+) -> (Counters, RunStatus, Option<command::Output>) {
+    // Prepare command
+    let mut cmd = command::Process::new(path, program_args);
+    cmd.envs(&args.env);
+    if let Some(cwd) = &args.cwd {
+        cmd.current_dir(cwd);
+    }
+    if let Some(stdin) = &args.stdin {
+        match File::open(stdin) {
+            Ok(file) => cmd.stdin(file),
+            Err(err) => {
+                println!("Error: could not open '{}': {}", stdin, err);
+                exit(1);
+            }
+        };
+    }
+    if let Some(core) = args.pin {
+        cmd.pin(core);
+    }
+    if let Some(nice) = args.nice {
+        cmd.nice(nice);
+    }
+    if args.quiet || args.capture {
+        cmd.capture_output();
+    }
+    if args.verbose && !args.quiet {
+        let name = Path::new(path).file_name().and_then(|name| name.to_str());
+        cmd.stream_output(name.unwrap_or(path));
+    }
+    let mut marker_fifo = args.marker_fifo.as_ref().map(|path| {
+        cmd.envs(&[(command::MARKER_FIFO_VAR.to_string(), path.clone())]);
+        command::MarkerFifo::open(path).unwrap_or_else(|err| {
+            println!("Error: could not open marker FIFO '{}': {}", path, err);
+            exit(1);
+        })
+    });
+
+    // Run & collect stats
+    let deadline = args
+        .timeout
+        .map(|timeout| Instant::now() + Duration::from_secs_f64(timeout));
+    if marker_fifo.is_none() {
+        monitor.start();
+    }
     let mut child = cmd.spawn().expect("Error: failed to run program");
-    let success = child.wait().expect("Error: failed to run program");
-    let stats = Stats::new(monitor.stop());
+    let region_counters = marker_fifo.as_mut().map(|fifo| {
+        let mut wait_marker = || {
+            fifo.wait_marker(&mut child, deadline)
+                .expect("Error: failed to read markers")
+        };
+        let started = wait_marker();
+        monitor.start();
+        let ended = started && wait_marker();
+        let counters = monitor.stop();
+        if !ended {
+            eprintln!("Warning: {} did not emit both markers", path);
+        }
+        counters
+    });
+    let status = match deadline {
+        Some(deadline) => child
+            .wait_timeout(deadline.saturating_duration_since(Instant::now()))
+            .expect("Error: failed to run program"),
+        None => Some(child.wait().expect("Error: failed to run program")),
+    };
+    let mut counters = region_counters.unwrap_or_else(|| monitor.stop());
+    counters.peak_rss_kb = child.peak_rss_kb();
+    let output = child.output();
+    // In verbose mode the standard error has already been streamed
+    if let Some(output) = output.as_ref().filter(|_| !args.quiet && !args.verbose) {
+        eprint!("{}", output.stderr);
+    }
 
     // Signal potential errors
-    if !success.success() {
-        println!("Command {} returned with non-zero exit code", path);
+    let status = RunStatus::new(status);
+    if status.timed_out {
+        eprintln!(
+            "Command {} timed out after {}s and was killed",
+            path,
+            args.timeout.unwrap_or_default()
+        );
+    } else if !status.success {
+        eprintln!("Command {} returned with non-zero exit code", path);
     }
-    stats
+    (counters, status, output)
 }
 
-fn benchmark_shared<P: ExternalProgram>(path: &str, args: &str) -> Stats {
-    let mut monitor = Monitor::new();
-    let program = P::load(path);
-    program.init(args);
+/// Load the library once, then measure each run of the program.
+fn benchmark_shared<P: ExternalProgram>(
+    path: &str,
+    program_args: &str,
+    args: &Args,
+    monitor: &mut Monitor,
+) -> Stats {
+    let program = match P::load(path) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("Error: could not load '{}': {}", path, err);
+            exit(1);
+        }
+    };
+    program.init(program_args);
 
     // Measurement
-    monitor.start();
-    program.run();
-    let stats = Stats::new(monitor.stop());
+    let stats = measure(args, path, || {
+        monitor.start();
+        let tracker = if args.count_threads {
+            Some(ThreadTracker::start())
+        } else {
+            None
+        };
+        let target_metric = program.run();
+        let threads = tracker.map(ThreadTracker::stop);
+        let mut counters = monitor.stop();
+        counters.target_metric = target_metric;
+        counters.threads = threads;
+        counters
+    });
 
-    // Cleanup & display
+    // Cleanup
     program.cleanup();
+    program.unload();
     stats
 }
 
@@ -76,3 +580,266 @@ fn set_signal_handler() {
         exit(0);
     }).expect("Could not set signal handler.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::Clap;
+
+    #[test]
+    fn json_output_to_file() {
+        let stats = Stats::new(Counters {
+            task_clock: Some(500_000),
+            wall_clock: 1_000_000,
+            ..Counters::default()
+        });
+        let path = std::env::temp_dir().join("bench_test_output/nested/stats.json");
+        write_output(&path, &serde_json::to_string(&stats).unwrap()).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(json["cpu_usage"], 0.5);
+    }
+
+    #[test]
+    fn html_report_to_file() {
+        let stats = Stats::new(Counters {
+            cycles: Some(2_000_000),
+            instructions: Some(1_000_000),
+            wall_clock: 1_000_000,
+            ..Counters::default()
+        });
+        let path = std::env::temp_dir().join("bench_test_output/report.html");
+        write_output(&path, &Report::new(&stats, None).html()).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("<svg"));
+        for metric in &["cycles", "instructions", "instr_per_cycle", "execution_time"] {
+            assert!(content.contains(&format!("<th>{}</th>", metric)));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn count_threads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/threads.so");
+        let args = Args::parse_from(["bench", "-c", path, "--count-threads"]);
+        let (stats, _) = benchmark(&args, path, "", &[Event::TaskClock]);
+
+        // The 4 busy threads, and the main thread waiting for them
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        let threads = json["threads"].as_u64().unwrap();
+        assert!((4..=5).contains(&threads), "{} threads", threads);
+        let efficiency = json["parallel_efficiency"].as_f64().unwrap();
+        assert!(efficiency > 0. && efficiency <= 1.1);
+    }
+
+    fn run_with_cycles(cycles: u64) -> Counters {
+        Counters {
+            cycles: Some(cycles),
+            wall_clock: 1_000_000,
+            ..Counters::default()
+        }
+    }
+
+    #[test]
+    fn auto_stable_measurement() {
+        let runs = run_until_stable(|| run_with_cycles(1_000_000), 50, 1.);
+        assert_eq!(runs.len(), AUTO_WINDOW);
+    }
+
+    #[test]
+    fn auto_noisy_measurement() {
+        let mut noise = [900_000, 1_000_000, 1_100_000].iter().cycle();
+        let runs = run_until_stable(|| run_with_cycles(*noise.next().unwrap()), 20, 1.);
+        assert_eq!(runs.len(), 20);
+
+        // The noise stops after a few runs.
+        let mut count = 0;
+        let runs = run_until_stable(
+            || {
+                count += 1;
+                run_with_cycles(if count < 8 { count * 100_000 } else { 1_000_000 })
+            },
+            50,
+            1.,
+        );
+        assert_eq!(runs.len(), 7 + AUTO_WINDOW);
+    }
+
+    #[test]
+    fn failed_program_exit_code() {
+        let args = Args::parse_from(["bench", "sh", "--counters", "task-clock"]);
+        let (_, failure) = benchmark(&args, "sh", "-c 'exit 3'", &[]);
+        assert_eq!(failure, Some(3));
+
+        let (_, failure) = benchmark(&args, "sh", "-c 'kill -9 $$'", &[]);
+        assert_eq!(failure, Some(TARGET_FAILED));
+
+        let (_, failure) = benchmark(&args, "true", "", &[]);
+        assert_eq!(failure, None);
+    }
+
+    #[test]
+    fn crash_status_in_json() {
+        let args = Args::parse_from(["bench", "sh", "--counters", "task-clock"]);
+        let (stats, failure) = benchmark(&args, "sh", "-c 'kill -SEGV $$'", &[]);
+        assert_eq!(failure, Some(TARGET_FAILED));
+        let json: serde_json::Value =
+            serde_json::from_str(&Report::new(&stats, None).json()).unwrap();
+        assert_eq!(json["status"]["success"], false);
+        assert_eq!(json["status"]["signal"], 11);
+        assert_eq!(json["status"]["exit_code"], serde_json::Value::Null);
+        assert_eq!(json["status"]["timed_out"], false);
+
+        let args = Args::parse_from(["bench", "sleep", "--timeout", "0.1"]);
+        let (stats, _) = benchmark(&args, "sleep", "5", &[]);
+        let json: serde_json::Value =
+            serde_json::from_str(&Report::new(&stats, None).json()).unwrap();
+        assert_eq!(json["status"]["timed_out"], true);
+
+        let (stats, failure) = benchmark(&args, "true", "", &[]);
+        assert_eq!(failure, None);
+        let json: serde_json::Value =
+            serde_json::from_str(&Report::new(&stats, None).json()).unwrap();
+        assert_eq!(json["status"]["success"], true);
+    }
+
+    #[test]
+    fn target_metric_in_json() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/run_result.so");
+        let args = Args::parse_from(["bench", "-c", path, "--counters", "task-clock"]);
+        let (stats, _) = benchmark(&args, path, "", &[]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["target_metric"], 42.0);
+    }
+
+    #[test]
+    fn several_runs_per_load() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/run_count.so");
+        let args = Args::parse_from([
+            "bench",
+            "-c",
+            path,
+            "--runs",
+            "5",
+            "--counters",
+            "task-clock",
+        ]);
+        let (stats, _) = benchmark(&args, path, "", &[]);
+
+        // The runs return 1 to 5 only if the library has been loaded and initialized once
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["target_metric"], 3.0);
+        assert_eq!(json["runs"], 5);
+    }
+
+    #[test]
+    fn runs_from_config() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/run_count.so");
+        let config = std::env::temp_dir().join("bench_test_config.toml");
+        fs::write(
+            &config,
+            format!(
+                "path = \"{}\"\nruns = 3\nwarmup = 1\ncounters = [\"task-clock\"]\n",
+                path
+            ),
+        )
+        .unwrap();
+        let args = config::parse_args(["bench", "-c", "--config", config.to_str().unwrap()])
+            .unwrap();
+        let (stats, _) = benchmark(&args, &args.paths[0], &args.args, &[]);
+
+        // The warmup run returns 1, the three measured runs return 2 to 4
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["runs"], 3);
+        assert_eq!(json["target_metric"], 3.0);
+    }
+
+    #[test]
+    fn marked_region() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/markers");
+        let fifo = std::env::temp_dir().join("bench_test_markers");
+        let fifo = fifo.to_str().unwrap();
+        let args = Args::parse_from([
+            "bench",
+            path,
+            "--marker-fifo",
+            fifo,
+            "--quiet",
+            "--counters",
+            "task-clock",
+        ]);
+        let (stats, failure) = benchmark(&args, path, "", &[]);
+
+        // The setup and teardown sleep for 300ms each, they are not measured
+        assert_eq!(failure, None);
+        assert!(stats.execution_time() < 0.2);
+    }
+
+    #[test]
+    fn sweep_speedup() {
+        let args = Args::parse_from([
+            "bench",
+            "sleep",
+            "--args",
+            "{time}",
+            "--sweep",
+            "time=0.2,0.1",
+            "--counters",
+            "task-clock",
+        ]);
+        let (sweep, failure) = benchmark_sweep(&args, "sleep", &[]);
+        assert_eq!(failure, None);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&sweep).unwrap()).unwrap();
+        let values = json["values"].as_array().unwrap();
+        let times: Vec<f64> = values
+            .iter()
+            .map(|value| value["stats"]["execution_time"].as_f64().unwrap())
+            .collect();
+        assert_eq!(values[0]["speedup"], 1.0);
+        let speedup = values[1]["speedup"].as_f64().unwrap();
+        assert!((speedup - times[0] / times[1]).abs() < 1e-9);
+        assert!(speedup > 1.5);
+    }
+
+    #[test]
+    fn several_paths() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/simple.so");
+        let args = Args::parse_from(["bench", "-c", path, path, "--counters", "task-clock"]);
+        assert_eq!(args.paths.len(), 2);
+        let targets = args
+            .paths
+            .iter()
+            .map(|path| Target {
+                path: path.clone(),
+                args: String::new(),
+            })
+            .collect();
+        let (suite, failure) = benchmark_targets(&args, targets, &[]);
+        assert_eq!(failure, None);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&suite).unwrap()).unwrap();
+        assert_eq!(json["targets"].as_array().unwrap().len(), 2);
+        // The header, the separator, the two targets and the geometric mean
+        assert_eq!(suite.markdown().lines().count(), 5);
+    }
+
+    #[test]
+    fn captured_stdout_in_json() {
+        let args = Args::parse_from(["bench", "echo", "--capture", "--counters", "task-clock"]);
+        let (stats, _) = benchmark(&args, "echo", "hello", &[]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["stdout"], "hello\n");
+    }
+}
@@ -1,167 +1,312 @@
 //! Monitor
 //!
 //! A module to collect statistics about the program running time.
-use perf_event::events::{Hardware, Software};
-use perf_event::{Builder, CountAndTime, Counter};
-use std::time::Instant;
+//!
+//! The counters are collected with `perf_event` on Linux. The other platforms fall back to the
+//! wall clock and the resource usage of the processes, the other counts are `None`.
+use crate::raw_event::RawEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+mod perf;
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+mod timing;
+
+/// The monitor of the current platform.
+#[cfg(target_os = "linux")]
+pub type Monitor = perf::PerfMonitor;
+#[cfg(not(target_os = "linux"))]
+pub type Monitor = timing::TimingMonitor;
+
+/// The events that can be monitored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Cycles,
+    RefCycles,
+    Instructions,
+    StalledCyclesFrontend,
+    StalledCyclesBackend,
+    TaskClock,
+    CacheMisses,
+    CacheReferences,
+    PageFaults,
+    ContextSwitches,
+    DtlbMisses,
+    ItlbMisses,
+}
 
 /// Measure statistics about the program execution.
-pub struct Monitor {
-    cycles: Counter,
-    ref_cycles: Counter,
-    instructions: Counter,
-    task_clock: Counter,
-    cache_misses: Counter,
-    cache_references: Counter,
-    start_time: Instant,
+pub trait Monitoring: Sized {
+    /// Create a monitor collecting only the given events.
+    ///
+    /// Events that can not be collected (e.g. because the hardware, the virtualization layer or the
+    /// platform does not support it) are skipped with a warning.
+    fn new(events: &[Event]) -> Self;
+
+    /// Create a monitor collecting the given events of the running process `pid`, instead of the
+    /// processes spawned by bench.
+    ///
+    /// Fails if the process does not exist, or if none of the events can be collected, e.g. because
+    /// the permission to observe the process is denied.
+    fn attach(events: &[Event], pid: u32) -> io::Result<Self>;
+
+    /// Also collect the given raw events.
+    fn raw_events(self, events: &[RawEvent]) -> Self;
+
+    /// Also break the cycles, instructions and task clock down per core.
+    fn per_core(self) -> Self;
+
+    /// Also sample the counters every `interval` while the monitor is running.
+    fn sample_every(self, interval: Duration) -> Self;
+
+    /// Start monitoring events.
+    ///
+    /// The counts are reset, so that a monitor can be started and stopped several times.
+    fn start(&mut self);
+
+    /// Stop monitoring events and return the collected statistics.
+    fn stop(&mut self) -> Counters;
 }
 
 /// Raw counts of events collected during program execution program execution.
 ///
 /// When the not enought counters are available on the hardware the counts are
 /// estimations based on the time the counter was active.
+///
+/// Counts are `None` when the corresponding event was not monitored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Counters {
     /// Total number of cycles.
-    pub cycles: u64,
+    pub cycles: Option<u64>,
     /// Total number of cycles, not affected by frequency scaling.
-    pub ref_cycles: u64,
+    pub ref_cycles: Option<u64>,
     /// Total number of instructions executed.
-    pub instructions: u64,
+    pub instructions: Option<u64>,
+    /// Cycles during which the frontend (fetch and decode) could not issue instructions.
+    pub stalled_cycles_frontend: Option<u64>,
+    /// Cycles during which the backend (execution and memory) could not retire instructions.
+    pub stalled_cycles_backend: Option<u64>,
     /// Total task clock, the sum of active time of all CPUs, in nano seconds.
-    pub task_clock: u64,
+    pub task_clock: Option<u64>,
     /// Wall clock time, in nano seconds.
     pub wall_clock: u64,
     /// Total cache misses, usually only last level caches are counted.
-    pub cache_misses: u64,
+    pub cache_misses: Option<u64>,
     /// Total cache access, usually only last level caches are counted.
-    pub cache_references: u64,
+    pub cache_references: Option<u64>,
+    /// Total number of page faults.
+    pub page_faults: Option<u64>,
+    /// Total number of context switches.
+    pub context_switches: Option<u64>,
+    /// Data TLB misses on loads.
+    pub dtlb_misses: Option<u64>,
+    /// Instruction TLB misses.
+    pub itlb_misses: Option<u64>,
+    /// Peak resident set size, in kilobytes.
+    pub peak_rss_kb: Option<u64>,
+    /// Number of threads observed while running the program, if tracked.
+    pub threads: Option<u64>,
+    /// Counts of the raw events, by label.
+    pub raw: Vec<(String, u64)>,
+    /// Metric reported by the benchmarked program, if any.
+    pub target_metric: Option<f64>,
+    /// Breakdown of the counts per core, if requested.
+    pub per_core: Vec<CoreCounts>,
+    /// Counts of each sampling interval, if requested.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_series: Vec<Counters>,
 }
 
-impl Monitor {
-    pub fn new() -> Self {
-        let cycles = Builder::new()
-            .kind(Hardware::CPU_CYCLES)
-            .inherit(true)
-            .build()
-            .expect("Failed to create cycles monitor");
-        let ref_cycles = Builder::new()
-            .kind(Hardware::REF_CPU_CYCLES)
-            .inherit(true)
-            .build()
-            .expect("Failed to create reference cycles monitor");
-        let instructions = Builder::new()
-            .kind(Hardware::INSTRUCTIONS)
-            .inherit(true)
-            .build()
-            .expect("Failed to create instructions monitor");
-        let task_clock = Builder::new()
-            .kind(Software::TASK_CLOCK)
-            .inherit(true)
-            .build()
-            .expect("Failed to create task_clock monitor");
-        let cache_misses = Builder::new()
-            .kind(Hardware::CACHE_MISSES)
-            .inherit(true)
-            .build()
-            .expect("Failed to create cache misses monitor");
-        let cache_references = Builder::new()
-            .kind(Hardware::CACHE_REFERENCES)
-            .inherit(true)
-            .build()
-            .expect("Failed to create cache references monitor");
-        Self {
-            cycles,
-            ref_cycles,
-            instructions,
-            task_clock,
-            cache_misses,
-            cache_references,
-            start_time: Instant::now(),
+/// The counts of the events that happened on a single core.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoreCounts {
+    pub cpu: usize,
+    pub cycles: Option<u64>,
+    pub instructions: Option<u64>,
+    /// Time spent on the core, in nano seconds.
+    pub task_clock: Option<u64>,
+}
+
+impl Event {
+    /// All the events supported by the monitor.
+    pub const ALL: [Event; 12] = [
+        Event::Cycles,
+        Event::RefCycles,
+        Event::Instructions,
+        Event::StalledCyclesFrontend,
+        Event::StalledCyclesBackend,
+        Event::TaskClock,
+        Event::CacheMisses,
+        Event::CacheReferences,
+        Event::PageFaults,
+        Event::ContextSwitches,
+        Event::DtlbMisses,
+        Event::ItlbMisses,
+    ];
+
+    /// The name of the event, as accepted by the `--counters` flag.
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Cycles => "cycles",
+            Event::RefCycles => "ref-cycles",
+            Event::Instructions => "instructions",
+            Event::StalledCyclesFrontend => "stalled-cycles-frontend",
+            Event::StalledCyclesBackend => "stalled-cycles-backend",
+            Event::TaskClock => "task-clock",
+            Event::CacheMisses => "cache-misses",
+            Event::CacheReferences => "cache-references",
+            Event::PageFaults => "page-faults",
+            Event::ContextSwitches => "context-switches",
+            Event::DtlbMisses => "dTLB-load-misses",
+            Event::ItlbMisses => "iTLB-load-misses",
         }
     }
+}
 
-    /// Start monitoring events.
-    pub fn start(&mut self) {
-        self.start_time = Instant::now();
-        self.task_clock
-            .enable()
-            .expect("Failed to start task_clock");
-        self.cycles.enable().expect("Failed to start cycles");
-        self.ref_cycles
-            .enable()
-            .expect("Failed to start reference cyles");
-        self.instructions
-            .enable()
-            .expect("Failed to start instructions");
-        self.cache_references
-            .enable()
-            .expect("Failed to start cache references");
-        self.cache_misses
-            .enable()
-            .expect("Failed to start cache misses");
+impl FromStr for Event {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Event::ALL
+            .iter()
+            .copied()
+            .find(|event| event.name() == s)
+            .ok_or_else(|| format!("unknown counter '{}'", s))
     }
+}
 
-    /// Stop moitoring events and return the collected statistics.
-    pub fn stop(&mut self) -> Counters {
-        // Stop counters
-        let elapsed = self.start_time.elapsed().as_nanos();
-        self.task_clock
-            .disable()
-            .expect("Failed to stop task_clock");
-        self.cycles.disable().expect("Failed to stop cycles");
-        self.instructions
-            .disable()
-            .expect("Failed to stop instructions");
-        self.cache_references
-            .disable()
-            .expect("Failed to disable cache references");
-        self.cache_misses
-            .disable()
-            .expect("Failes to disable cache misses");
-        // Read counts and running times
-        let task_clock = self
-            .task_clock
-            .read_count_and_time()
-            .expect("Could not read task_clock");
-        let cycles = self
-            .cycles
-            .read_count_and_time()
-            .expect("Could not read cycles");
-        let ref_cycles = self
-            .ref_cycles
-            .read_count_and_time()
-            .expect("Could not read reference cycles");
-        let instructions = self
-            .instructions
-            .read_count_and_time()
-            .expect("Could not read instructions");
-        let cache_references = self
-            .cache_references
-            .read_count_and_time()
-            .expect("Could not read cache references");
-        let cache_misses = self
-            .cache_misses
-            .read_count_and_time()
-            .expect("Could not read cache misses");
-        // Estimate real counts
-        let task_clock = estimate_real_count(task_clock);
-        let cycles = estimate_real_count(cycles);
-        let ref_cycles = estimate_real_count(ref_cycles);
-        let instructions = estimate_real_count(instructions);
-        let cache_misses = estimate_real_count(cache_misses);
-        let cache_references = estimate_real_count(cache_references);
-        Counters {
-            cycles,
-            ref_cycles,
-            instructions,
-            task_clock,
-            cache_misses,
-            cache_references,
-            wall_clock: elapsed as u64,
+/// Parse a comma-separated list of events, `all` selects all the supported events.
+pub fn parse_events(list: &str) -> Result<Vec<Event>, String> {
+    if list == "all" {
+        return Ok(Event::ALL.to_vec());
+    }
+    let mut events = Vec::new();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let event = name.parse()?;
+        if !events.contains(&event) {
+            events.push(event);
         }
     }
+    Ok(events)
+}
+
+impl Counters {
+    /// Record the count of a given event.
+    pub fn set(&mut self, event: Event, count: u64) {
+        *self.slot(event) = Some(count);
+    }
+
+    /// Add to the count of a given event, e.g. to sum the counts of several threads.
+    pub fn add(&mut self, event: Event, count: u64) {
+        let slot = self.slot(event);
+        *slot = Some(slot.unwrap_or(0).saturating_add(count));
+    }
+
+    fn slot(&mut self, event: Event) -> &mut Option<u64> {
+        match event {
+            Event::Cycles => &mut self.cycles,
+            Event::RefCycles => &mut self.ref_cycles,
+            Event::Instructions => &mut self.instructions,
+            Event::StalledCyclesFrontend => &mut self.stalled_cycles_frontend,
+            Event::StalledCyclesBackend => &mut self.stalled_cycles_backend,
+            Event::TaskClock => &mut self.task_clock,
+            Event::CacheMisses => &mut self.cache_misses,
+            Event::CacheReferences => &mut self.cache_references,
+            Event::PageFaults => &mut self.page_faults,
+            Event::ContextSwitches => &mut self.context_switches,
+            Event::DtlbMisses => &mut self.dtlb_misses,
+            Event::ItlbMisses => &mut self.itlb_misses,
+        }
+    }
+}
+
+fn warn_unavailable(name: &str, err: io::Error) {
+    eprintln!(
+        "Warning: could not create {} counter, it will not be reported ({})",
+        name, err
+    );
+}
+
+/// Track the threads of the bench process, by listing `/proc/self/task` at regular intervals.
+///
+/// Threads living for less than `THREAD_SAMPLING_INTERVAL` might not be observed.
+pub struct ThreadTracker {
+    stop: mpsc::Sender<()>,
+    thread: thread::JoinHandle<(HashSet<u64>, u64)>,
+    /// The threads that existed before the tracking started.
+    existing: HashSet<u64>,
+}
+
+/// Interval between two listings of the threads.
+const THREAD_SAMPLING_INTERVAL: Duration = Duration::from_millis(1);
+
+impl ThreadTracker {
+    pub fn start() -> Self {
+        let existing = list_threads("self");
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut observed = HashSet::new();
+            loop {
+                observed.extend(list_threads("self"));
+                if stopped.recv_timeout(THREAD_SAMPLING_INTERVAL) != Err(RecvTimeoutError::Timeout)
+                {
+                    return (observed, own_tid());
+                }
+            }
+        });
+        Self {
+            stop,
+            thread,
+            existing,
+        }
+    }
+
+    /// Stop tracking, and return the number of threads that ran in the meantime: the calling
+    /// thread and the threads it spawned.
+    pub fn stop(self) -> u64 {
+        let _ = self.stop.send(());
+        let (observed, own_tid) = self
+            .thread
+            .join()
+            .expect("The thread tracking thread panicked");
+        let spawned = observed
+            .difference(&self.existing)
+            .filter(|&&tid| tid != own_tid)
+            .count();
+        1 + spawned as u64
+    }
+}
+
+/// Return the IDs of the threads of `process`, either a PID or `self` for the bench process.
+fn list_threads(process: &str) -> HashSet<u64> {
+    fs::read_dir(format!("/proc/{}/task", process))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Return the ID of the calling thread, as listed by `list_threads`.
+#[cfg(target_os = "linux")]
+fn own_tid() -> u64 {
+    nix::unistd::gettid().as_raw() as u64
 }
 
-fn estimate_real_count(cat: CountAndTime) -> u64 {
-    (cat.count as u128 * cat.time_enabled as u128 / cat.time_running as u128) as u64
+/// Threads are not listed on this platform, only the calling thread is reported.
+#[cfg(not(target_os = "linux"))]
+fn own_tid() -> u64 {
+    0
 }
@@ -0,0 +1,692 @@
+//! Perf
+//!
+//! The monitor of Linux, collecting the counters with `perf_event`.
+use super::{list_threads, warn_unavailable, CoreCounts, Counters, Event, Monitoring};
+use crate::raw_event::{RawCounter, RawEvent};
+use perf_event::events::{self, Cache, CacheOp, CacheResult, Hardware, Software, WhichCache};
+use perf_event::{Builder, CountAndTime, Counter};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::mem;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Measure statistics about the program execution with `perf_event`.
+pub struct PerfMonitor {
+    counters: EventCounters,
+    raw_counters: Vec<(String, RawCounter)>,
+    /// Counters restricted to a single CPU, used for the per-core breakdown.
+    core_counters: Vec<(usize, Event, Counter)>,
+    /// Time enabled and running of each counter (followed by the raw and per-core counters) when
+    /// the monitor was last started.
+    start_times: Vec<(u64, u64)>,
+    start_time: Instant,
+    /// Interval at which the counters are sampled, if requested.
+    sample_interval: Option<Duration>,
+    /// The thread sampling the counters while the monitor is running.
+    sampler: Option<Sampler>,
+}
+
+/// A background thread reading the counters at regular intervals.
+///
+/// The sampler owns the counters while the monitor is running, and gives them back along with the
+/// time series once stopped.
+struct Sampler {
+    stop: mpsc::Sender<()>,
+    thread: thread::JoinHandle<(EventCounters, Vec<Counters>)>,
+}
+
+/// The counters of the monitored events.
+type EventCounters = Vec<(Event, Counter)>;
+
+impl Event {
+    fn kind(self) -> events::Event {
+        match self {
+            Event::Cycles => Hardware::CPU_CYCLES.into(),
+            Event::RefCycles => Hardware::REF_CPU_CYCLES.into(),
+            Event::Instructions => Hardware::INSTRUCTIONS.into(),
+            Event::StalledCyclesFrontend => Hardware::STALLED_CYCLES_FRONTEND.into(),
+            Event::StalledCyclesBackend => Hardware::STALLED_CYCLES_BACKEND.into(),
+            Event::TaskClock => Software::TASK_CLOCK.into(),
+            Event::CacheMisses => Hardware::CACHE_MISSES.into(),
+            Event::CacheReferences => Hardware::CACHE_REFERENCES.into(),
+            Event::PageFaults => Software::PAGE_FAULTS.into(),
+            Event::ContextSwitches => Software::CONTEXT_SWITCHES.into(),
+            Event::DtlbMisses => tlb_misses(WhichCache::DTLB),
+            Event::ItlbMisses => tlb_misses(WhichCache::ITLB),
+        }
+    }
+}
+
+impl CoreCounts {
+    fn set(&mut self, event: Event, count: u64) {
+        match event {
+            Event::Cycles => self.cycles = Some(count),
+            Event::Instructions => self.instructions = Some(count),
+            Event::TaskClock => self.task_clock = Some(count),
+            _ => (),
+        }
+    }
+}
+
+impl PerfMonitor {
+    /// Create a monitor using a custom function to build the counters.
+    fn with_builder<F>(events: &[Event], build: F) -> Self
+    where
+        F: Fn(Event) -> io::Result<Counter>,
+    {
+        let counters = events
+            .iter()
+            .filter_map(|&event| match build(event) {
+                Ok(counter) => Some((event, counter)),
+                Err(err) => {
+                    warn_unavailable(event.name(), err);
+                    None
+                }
+            })
+            .collect();
+        Self::from_counters(counters)
+    }
+
+    fn from_counters(counters: EventCounters) -> Self {
+        Self {
+            counters,
+            raw_counters: Vec::new(),
+            core_counters: Vec::new(),
+            start_times: Vec::new(),
+            start_time: Instant::now(),
+            sample_interval: None,
+            sampler: None,
+        }
+    }
+}
+
+impl Monitoring for PerfMonitor {
+    fn new(events: &[Event]) -> Self {
+        Self::with_builder(events, build_counter)
+    }
+
+    /// One counter per event is opened on each thread of the process, an event is skipped with a
+    /// warning if it can not be collected on one of the threads.
+    fn attach(events: &[Event], pid: u32) -> io::Result<Self> {
+        let threads = list_threads(&pid.to_string());
+        if threads.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no process with PID {}", pid),
+            ));
+        }
+        let mut counters = Vec::new();
+        let mut errors = Vec::new();
+        for &event in events {
+            let per_thread: io::Result<Vec<Counter>> = threads
+                .iter()
+                .map(|&tid| {
+                    Builder::new()
+                        .kind(event.kind())
+                        .observe_pid(tid as i32)
+                        .inherit(true)
+                        .build()
+                })
+                .collect();
+            match per_thread {
+                Ok(per_thread) => counters.extend(per_thread.into_iter().map(|c| (event, c))),
+                Err(err) => errors.push((event, err)),
+            }
+        }
+        if counters.is_empty() && !errors.is_empty() {
+            let (_, err) = errors.swap_remove(0);
+            return Err(match err.kind() {
+                io::ErrorKind::PermissionDenied => io::Error::new(
+                    err.kind(),
+                    format!(
+                        "{}, observing another process requires the permission to trace it (see \
+                         /proc/sys/kernel/perf_event_paranoid)",
+                        err
+                    ),
+                ),
+                _ => err,
+            });
+        }
+        for (event, err) in errors {
+            warn_unavailable(event.name(), err);
+        }
+        Ok(Self::from_counters(counters))
+    }
+
+    fn raw_events(mut self, events: &[RawEvent]) -> Self {
+        for event in events {
+            match RawCounter::new(event) {
+                Ok(counter) => self.raw_counters.push((event.label.clone(), counter)),
+                Err(err) => warn_unavailable(&event.label, err),
+            }
+        }
+        self
+    }
+
+    /// One counter per event is opened on each online CPU, the breakdown reveals load imbalance
+    /// in multi-threaded programs.
+    fn per_core(mut self) -> Self {
+        let events: Vec<Event> = self
+            .counters
+            .iter()
+            .map(|(event, _)| *event)
+            .filter(|event| PER_CORE_EVENTS.contains(event))
+            .collect();
+        for cpu in online_cpus() {
+            for &event in &events {
+                let counter = Builder::new()
+                    .kind(event.kind())
+                    .one_cpu(cpu)
+                    .inherit(true)
+                    .build();
+                match counter {
+                    Ok(counter) => self.core_counters.push((cpu, event, counter)),
+                    Err(err) => warn_unavailable(&format!("{} (cpu {})", event.name(), cpu), err),
+                }
+            }
+        }
+        self
+    }
+
+    /// The counters are read from a background thread without being stopped, the counts of each
+    /// interval are reported in `Counters::time_series`. Raw events and the per-core breakdown are
+    /// not sampled.
+    fn sample_every(mut self, interval: Duration) -> Self {
+        self.sample_interval = Some(interval);
+        self
+    }
+
+    fn start(&mut self) {
+        let start_times = &mut self.start_times;
+        start_times.clear();
+        for (event, counter) in &mut self.counters {
+            counter
+                .reset()
+                .and_then(|_| counter.read_count_and_time())
+                .map(|cat| start_times.push((cat.time_enabled, cat.time_running)))
+                .unwrap_or_else(|_| panic!("Failed to reset {}", event.name()));
+        }
+        for (label, counter) in &mut self.raw_counters {
+            counter
+                .reset()
+                .and_then(|_| counter.read_count_and_time())
+                .map(|cat| start_times.push((cat.time_enabled, cat.time_running)))
+                .unwrap_or_else(|_| panic!("Failed to reset {}", label));
+        }
+        for (cpu, event, counter) in &mut self.core_counters {
+            counter
+                .reset()
+                .and_then(|_| counter.read_count_and_time())
+                .map(|cat| start_times.push((cat.time_enabled, cat.time_running)))
+                .unwrap_or_else(|_| panic!("Failed to reset {} on cpu {}", event.name(), cpu));
+        }
+        self.start_time = Instant::now();
+        for (event, counter) in &mut self.counters {
+            counter
+                .enable()
+                .unwrap_or_else(|_| panic!("Failed to start {}", event.name()));
+        }
+        for (label, counter) in &mut self.raw_counters {
+            counter
+                .enable()
+                .unwrap_or_else(|_| panic!("Failed to start {}", label));
+        }
+        for (cpu, event, counter) in &mut self.core_counters {
+            counter
+                .enable()
+                .unwrap_or_else(|_| panic!("Failed to start {} on cpu {}", event.name(), cpu));
+        }
+        if let Some(interval) = self.sample_interval {
+            let start_times = self.start_times[..self.counters.len()].to_vec();
+            let counters = mem::take(&mut self.counters);
+            self.sampler = Some(Sampler::spawn(counters, start_times, interval));
+        }
+    }
+
+    fn stop(&mut self) -> Counters {
+        // Stop counters
+        let elapsed = self.start_time.elapsed().as_nanos();
+        let time_series = match self.sampler.take() {
+            Some(sampler) => {
+                let (counters, time_series) = sampler.join();
+                self.counters = counters;
+                time_series
+            }
+            None => Vec::new(),
+        };
+        for (event, counter) in &mut self.counters {
+            counter
+                .disable()
+                .unwrap_or_else(|_| panic!("Failed to stop {}", event.name()));
+        }
+        for (label, counter) in &mut self.raw_counters {
+            counter
+                .disable()
+                .unwrap_or_else(|_| panic!("Failed to stop {}", label));
+        }
+        for (cpu, event, counter) in &mut self.core_counters {
+            counter
+                .disable()
+                .unwrap_or_else(|_| panic!("Failed to stop {} on cpu {}", event.name(), cpu));
+        }
+        // Read counts and running times, then estimate real counts
+        // Very short runs may not see the clock move, the wall clock is used as a denominator
+        let mut counts = Counters {
+            wall_clock: (elapsed as u64).max(1),
+            time_series,
+            ..Counters::default()
+        };
+        let mut start_times = self.start_times.iter();
+        for (event, counter) in &mut self.counters {
+            let count = counter
+                .read_count_and_time()
+                .unwrap_or_else(|_| panic!("Could not read {}", event.name()));
+            let count = since(count, start_times.next().copied());
+            counts.add(*event, estimate_real_count(count));
+        }
+        for (label, counter) in &mut self.raw_counters {
+            let count = counter
+                .read_count_and_time()
+                .unwrap_or_else(|_| panic!("Could not read {}", label));
+            let count = since(count, start_times.next().copied());
+            counts.raw.push((label.clone(), estimate_real_count(count)));
+        }
+        for (cpu, event, counter) in &mut self.core_counters {
+            let count = counter
+                .read_count_and_time()
+                .unwrap_or_else(|_| panic!("Could not read {} on cpu {}", event.name(), cpu));
+            let count = since(count, start_times.next().copied());
+            if counts.per_core.last().map(|core| core.cpu) != Some(*cpu) {
+                counts.per_core.push(CoreCounts {
+                    cpu: *cpu,
+                    ..CoreCounts::default()
+                });
+            }
+            let core = counts.per_core.last_mut().unwrap();
+            core.set(*event, estimate_real_count(count));
+        }
+        counts
+    }
+}
+
+impl Sampler {
+    /// Read `counters` every `interval` until stopped, the last interval ends when the sampler is
+    /// stopped.
+    fn spawn(
+        mut counters: EventCounters,
+        start_times: Vec<(u64, u64)>,
+        interval: Duration,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut time_series = Vec::new();
+            let mut previous_counts = vec![0; counters.len()];
+            let mut previous_time = Instant::now();
+            loop {
+                let is_last = stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout);
+                let now = Instant::now();
+                let mut sample = Counters {
+                    wall_clock: now.duration_since(previous_time).as_nanos() as u64,
+                    ..Counters::default()
+                };
+                previous_time = now;
+                let previous = previous_counts.iter_mut().zip(&start_times);
+                for ((event, counter), (previous, start)) in counters.iter_mut().zip(previous) {
+                    // Read without disabling the counter, the counts are cumulative
+                    if let Ok(count) = counter.read_count_and_time() {
+                        let count = estimate_real_count(since(count, Some(*start)));
+                        sample.add(*event, count.saturating_sub(*previous));
+                        *previous = count;
+                    }
+                }
+                time_series.push(sample);
+                if is_last {
+                    return (counters, time_series);
+                }
+            }
+        });
+        Self { stop, thread }
+    }
+
+    /// Take a last sample, then return the counters and the time series.
+    fn join(self) -> (EventCounters, Vec<Counters>) {
+        // The sampler also stops if the channel is closed
+        let _ = self.stop.send(());
+        self.thread.join().expect("The sampling thread panicked")
+    }
+}
+
+/// The events broken down per core with `Monitoring::per_core`.
+const PER_CORE_EVENTS: [Event; 3] = [Event::Cycles, Event::Instructions, Event::TaskClock];
+
+/// Return the online CPUs, as listed in `/sys/devices/system/cpu/online` (e.g. `0-3,6`).
+fn online_cpus() -> Vec<usize> {
+    let online = fs::read_to_string("/sys/devices/system/cpu/online").unwrap_or_default();
+    let mut cpus = Vec::new();
+    for range in online.trim().split(',').filter(|range| !range.is_empty()) {
+        let mut bounds = range.split('-').map(|bound| bound.parse::<usize>());
+        match (bounds.next(), bounds.next()) {
+            (Some(Ok(first)), Some(Ok(last))) => cpus.extend(first..=last),
+            (Some(Ok(cpu)), None) => cpus.push(cpu),
+            _ => (),
+        }
+    }
+    cpus
+}
+
+/// Read misses of the given TLB, whose support depends on the microarchitecture.
+fn tlb_misses(which: WhichCache) -> events::Event {
+    Cache {
+        which,
+        operation: CacheOp::READ,
+        result: CacheResult::MISS,
+    }
+    .into()
+}
+
+fn build_counter(event: Event) -> io::Result<Counter> {
+    Builder::new().kind(event.kind()).inherit(true).build()
+}
+
+/// Remove the time the counter was enabled and running before the monitor was started.
+fn since(cat: CountAndTime, start: Option<(u64, u64)>) -> CountAndTime {
+    let (enabled, running) = start.unwrap_or_default();
+    CountAndTime {
+        count: cat.count,
+        time_enabled: cat.time_enabled.saturating_sub(enabled),
+        time_running: cat.time_running.saturating_sub(running),
+    }
+}
+
+/// The largest factor applied to a count to account for multiplexing, beyond which the scaled count
+/// is considered meaningless.
+const MAX_SCALING: u64 = 1000;
+
+/// Scale the count to account for the time the counter was not running due to multiplexing.
+///
+/// The raw count is returned if the counter never ran, or if it ran at least as long as it was
+/// enabled: a running time above the enabled time is reported by some multiplexing bugs. The
+/// scaled count is bounded to `MAX_SCALING` times the raw count.
+fn estimate_real_count(cat: CountAndTime) -> u64 {
+    if cat.time_running == 0 || cat.time_running >= cat.time_enabled {
+        return cat.count;
+    }
+    let scaled = cat.count as u128 * cat.time_enabled as u128 / cat.time_running as u128;
+    let max = cat.count as u128 * MAX_SCALING as u128;
+    u64::try_from(scaled.min(max)).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Process;
+    use crate::stats::Stats;
+
+    #[test]
+    fn page_faults() {
+        let mut monitor = PerfMonitor::new(&[Event::PageFaults, Event::ContextSwitches]);
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=1")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+
+        assert!(counters.page_faults.unwrap() > 0);
+        assert!(counters.context_switches.is_some());
+        assert!(counters.cycles.is_none());
+    }
+
+    #[test]
+    fn attach() {
+        let mut sleeper = std::process::Command::new("sh")
+            .args(["-c", "while true; do :; done"])
+            .spawn()
+            .unwrap();
+        let monitor = PerfMonitor::attach(&[Event::Cycles, Event::TaskClock], sleeper.id());
+        let counters = monitor.map(|mut monitor| {
+            monitor.start();
+            thread::sleep(Duration::from_millis(100));
+            monitor.stop()
+        });
+        sleeper.kill().unwrap();
+        sleeper.wait().unwrap();
+        let counters = counters.unwrap();
+
+        // Cycles are not available on all machines (e.g. virtual machines)
+        if let Some(cycles) = counters.cycles {
+            assert!(cycles > 0);
+        }
+        assert!(counters.task_clock.unwrap() > 0);
+        assert!(counters.wall_clock >= 100_000_000);
+    }
+
+    #[test]
+    fn attach_missing_process() {
+        let err = PerfMonitor::attach(&[Event::TaskClock], u32::MAX)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn stall_rates() {
+        // Stalled cycles are not available on all microarchitectures
+        let mut monitor = PerfMonitor::new(&[
+            Event::Cycles,
+            Event::StalledCyclesFrontend,
+            Event::StalledCyclesBackend,
+        ]);
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=4")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let stats = Stats::new(monitor.stop());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        for rate in &["frontend_stall_rate", "backend_stall_rate"] {
+            if let Some(rate) = json[rate].as_f64() {
+                assert!((0. ..=1.).contains(&rate), "{} out of bounds", rate);
+            }
+        }
+    }
+
+    #[test]
+    fn tlb_misses() {
+        // TLB events are not available on all microarchitectures
+        let mut monitor = PerfMonitor::new(&[Event::Instructions, Event::DtlbMisses]);
+        monitor.start();
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/build/pointer_chase");
+        let mut child = Process::new(path, "").capture_output().spawn().unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+        let misses = match counters.dtlb_misses {
+            Some(misses) => misses,
+            None => return,
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&Stats::new(counters)).unwrap()).unwrap();
+        assert_eq!(json["dtlb_misses"], misses);
+        if let Some(rate) = json["dtlb_misses_per_instr"].as_f64() {
+            assert!(rate >= 0. && rate.is_finite());
+        }
+    }
+
+    #[test]
+    fn restart_monitor() {
+        let mut monitor = PerfMonitor::new(&[Event::PageFaults]);
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=1")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let first = monitor.stop().page_faults.unwrap();
+
+        monitor.start();
+        let second = monitor.stop().page_faults.unwrap();
+        assert!(second < first);
+    }
+
+    #[test]
+    fn unavailable_counter() {
+        let events = [Event::TaskClock, Event::CacheMisses, Event::PageFaults];
+        let mut monitor = PerfMonitor::with_builder(&events, |event| match event {
+            Event::CacheMisses => Err(io::Error::other("not supported")),
+            _ => build_counter(event),
+        });
+        monitor.start();
+        let counters = monitor.stop();
+
+        assert!(counters.task_clock.is_some());
+        assert!(counters.page_faults.is_some());
+        assert!(counters.cache_misses.is_none());
+
+        let json = serde_json::to_string(&Stats::new(counters)).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(json["cpu_usage"].is_number());
+        assert!(json["cache_miss_rate"].is_null());
+    }
+
+    #[test]
+    fn raw_event() {
+        // Page faults, expressed as a raw software event.
+        let raw = "raw-faults=0x1:0x2".parse().unwrap();
+        let mut monitor = PerfMonitor::new(&[Event::PageFaults]).raw_events(&[raw]);
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=1")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+
+        let faults = counters.page_faults.unwrap() as f64;
+        let (label, raw_faults) = &counters.raw[0];
+        assert_eq!(label, "raw-faults");
+        assert!((*raw_faults as f64 - faults).abs() <= faults * 0.1);
+    }
+
+    #[test]
+    fn per_core_breakdown() {
+        let mut monitor = PerfMonitor::new(&[Event::TaskClock, Event::PageFaults]).per_core();
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=4")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+
+        // Only the task clock is both monitored and broken down
+        assert_eq!(counters.per_core.len(), online_cpus().len());
+        assert!(counters
+            .per_core
+            .iter()
+            .all(|core| core.instructions.is_none()));
+        let total = counters.task_clock.unwrap() as f64;
+        let sum: u64 = counters
+            .per_core
+            .iter()
+            .map(|core| core.task_clock.unwrap())
+            .sum();
+        assert!((sum as f64 - total).abs() <= total * 0.1);
+    }
+
+    #[test]
+    fn sampling() {
+        let interval = Duration::from_millis(20);
+        let mut monitor = PerfMonitor::new(&[Event::TaskClock]).sample_every(interval);
+        monitor.start();
+        // An idle phase followed by a busy one
+        let mut child = Process::new(
+            "sh",
+            "-c 'sleep 0.2 && dd if=/dev/zero of=/dev/null bs=16M count=64'",
+        )
+        .spawn()
+        .unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+
+        let expected = counters.wall_clock as f64 / interval.as_nanos() as f64;
+        let nb_samples = counters.time_series.len() as f64;
+        assert!(
+            nb_samples >= expected * 0.5 && nb_samples <= expected + 2.,
+            "{} samples, expected about {}",
+            nb_samples,
+            expected
+        );
+        let total = counters.task_clock.unwrap() as f64;
+        let sum: u64 = counters
+            .time_series
+            .iter()
+            .map(|sample| sample.task_clock.unwrap())
+            .sum();
+        assert!((sum as f64 - total).abs() <= total * 0.1);
+
+        // The counters are given back once the sampler stopped
+        monitor.start();
+        assert!(monitor.stop().task_clock.is_some());
+    }
+
+    #[test]
+    fn counter_never_running() {
+        let cat = CountAndTime {
+            count: 0,
+            time_enabled: 1000,
+            time_running: 0,
+        };
+        assert_eq!(estimate_real_count(cat), 0);
+
+        let cat = CountAndTime {
+            count: 42,
+            time_enabled: 1000,
+            time_running: 0,
+        };
+        assert_eq!(estimate_real_count(cat), 42);
+    }
+
+    #[test]
+    fn counter_running_longer_than_enabled() {
+        let cat = CountAndTime {
+            count: 100,
+            time_enabled: 1000,
+            time_running: 4000,
+        };
+        assert_eq!(estimate_real_count(cat), 100);
+    }
+
+    #[test]
+    fn counter_multiplexed() {
+        let cat = CountAndTime {
+            count: 100,
+            time_enabled: 1000,
+            time_running: 250,
+        };
+        assert_eq!(estimate_real_count(cat), 400);
+
+        let cat = CountAndTime {
+            count: 100,
+            time_enabled: 1000,
+            time_running: 1000,
+        };
+        assert_eq!(estimate_real_count(cat), 100);
+
+        // The scaling is bounded, and the scaled count does not overflow
+        let cat = CountAndTime {
+            count: u64::MAX / 2,
+            time_enabled: u64::MAX,
+            time_running: 1,
+        };
+        assert_eq!(estimate_real_count(cat), u64::MAX);
+        let cat = CountAndTime {
+            count: 10,
+            time_enabled: u64::MAX,
+            time_running: 1,
+        };
+        assert_eq!(estimate_real_count(cat), 10 * MAX_SCALING);
+    }
+}
@@ -0,0 +1,177 @@
+//! Timing
+//!
+//! The monitor of the platforms without `perf_event`, relying on the wall clock and on the resource
+//! usage reported by `getrusage`.
+use super::{warn_unavailable, Counters, Event, Monitoring};
+use crate::raw_event::RawEvent;
+use std::io;
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+
+/// The events derived from the resource usage.
+const SUPPORTED_EVENTS: [Event; 3] = [Event::TaskClock, Event::PageFaults, Event::ContextSwitches];
+
+/// Measure the execution time and the resource usage of the program.
+///
+/// The resource usage covers the bench process and its terminated childs, only the task clock, the
+/// page faults and the context switches are available.
+pub struct TimingMonitor {
+    events: Vec<Event>,
+    start_usage: Usage,
+    start_time: Instant,
+}
+
+/// The resource usage of the bench process and of its terminated childs.
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    /// User and system time, in nano seconds.
+    cpu_time: u64,
+    page_faults: u64,
+    context_switches: u64,
+}
+
+impl Monitoring for TimingMonitor {
+    fn new(events: &[Event]) -> Self {
+        for event in events
+            .iter()
+            .filter(|event| !SUPPORTED_EVENTS.contains(event))
+        {
+            warn_unavailable(event.name(), unsupported());
+        }
+        Self {
+            events: events
+                .iter()
+                .copied()
+                .filter(|event| SUPPORTED_EVENTS.contains(event))
+                .collect(),
+            start_usage: Usage::default(),
+            start_time: Instant::now(),
+        }
+    }
+
+    fn attach(_events: &[Event], _pid: u32) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "attaching to a process is not supported on this platform",
+        ))
+    }
+
+    fn raw_events(self, events: &[RawEvent]) -> Self {
+        for event in events {
+            warn_unavailable(&event.label, unsupported());
+        }
+        self
+    }
+
+    fn per_core(self) -> Self {
+        eprintln!("Warning: the per-core breakdown is not supported on this platform");
+        self
+    }
+
+    fn sample_every(self, _interval: Duration) -> Self {
+        eprintln!("Warning: sampling the counters is not supported on this platform");
+        self
+    }
+
+    fn start(&mut self) {
+        self.start_usage = Usage::now();
+        self.start_time = Instant::now();
+    }
+
+    fn stop(&mut self) -> Counters {
+        let elapsed = self.start_time.elapsed().as_nanos();
+        let usage = Usage::now();
+        // Very short runs may not see the clock move, the wall clock is used as a denominator
+        let mut counts = Counters {
+            wall_clock: (elapsed as u64).max(1),
+            ..Counters::default()
+        };
+        for &event in &self.events {
+            let count = usage
+                .count(event)
+                .saturating_sub(self.start_usage.count(event));
+            counts.set(event, count);
+        }
+        counts
+    }
+}
+
+impl Usage {
+    fn now() -> Self {
+        let own = rusage(libc::RUSAGE_SELF);
+        let childs = rusage(libc::RUSAGE_CHILDREN);
+        let usages = [own, childs];
+        Self {
+            cpu_time: usages
+                .iter()
+                .map(|usage| nanoseconds(usage.ru_utime) + nanoseconds(usage.ru_stime))
+                .sum(),
+            page_faults: usages
+                .iter()
+                .map(|usage| (usage.ru_minflt + usage.ru_majflt) as u64)
+                .sum(),
+            context_switches: usages
+                .iter()
+                .map(|usage| (usage.ru_nvcsw + usage.ru_nivcsw) as u64)
+                .sum(),
+        }
+    }
+
+    /// The count of one of the `SUPPORTED_EVENTS`.
+    fn count(&self, event: Event) -> u64 {
+        match event {
+            Event::TaskClock => self.cpu_time,
+            Event::PageFaults => self.page_faults,
+            Event::ContextSwitches => self.context_switches,
+            _ => 0,
+        }
+    }
+}
+
+fn rusage(who: libc::c_int) -> libc::rusage {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: `getrusage` initializes `usage` when it succeeds, which it always does with
+    // `RUSAGE_SELF` or `RUSAGE_CHILDREN` and a valid pointer.
+    unsafe {
+        assert_eq!(libc::getrusage(who, usage.as_mut_ptr()), 0);
+        usage.assume_init()
+    }
+}
+
+fn nanoseconds(time: libc::timeval) -> u64 {
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_usec as u64 * 1000
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Process;
+    use crate::stats::Stats;
+
+    #[test]
+    fn execution_time() {
+        let events = [Event::Cycles, Event::TaskClock, Event::PageFaults];
+        let mut monitor = TimingMonitor::new(&events);
+        monitor.start();
+        let mut child = Process::new("dd", "if=/dev/zero of=/dev/null bs=16M count=4")
+            .spawn()
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let counters = monitor.stop();
+
+        assert!(counters.task_clock.unwrap() > 0);
+        assert!(counters.page_faults.unwrap() > 0);
+        assert!(counters.cycles.is_none());
+
+        let stats = Stats::new(counters);
+        assert!(stats.execution_time() > 0.);
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert!(json["cpu_usage"].is_number());
+        assert!(json["instr_per_cycle"].is_null());
+    }
+}
@@ -3,40 +3,170 @@
 /// This module is responsible for defining a foreign interface (C and Rust are supported) and
 /// loading the program as a shared object using `dlopen`.
 use crate::cli::{CArgs, RustArgs};
-use dlopen::wrapper::{Container, WrapperApi};
+use dlopen::wrapper::{Container, WrapperApi, WrapperMultiApi};
 use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::os::raw::{c_char, c_int};
+use std::path::Path;
+
+/// Version of the ABI implemented by bench.
+///
+/// Libraries can export a `bench_abi_version` function returning the version they have been built
+/// for, libraries without it are assumed to target version 0.
+pub const ABI_VERSION: u32 = 0;
 
 /// The interface to the external program ABI.
 pub trait ExternalProgram {
     /// Load the program at `path`.
-    fn load<P: AsRef<OsStr>>(path: P) -> Self;
+    fn load<P: AsRef<OsStr>>(path: P) -> Result<Self, LoadError>
+    where
+        Self: Sized;
 
     /// Call the initialization function of the loaded program.
     /// The arguments will be parsed and made available in the most natural form for all
     /// supported languages.
     fn init(&self, args: &str);
 
-    /// Run the actual job to benchmark.
-    fn run(&self);
+    /// Run the actual job to benchmark, and return the metric it reported, if any.
+    fn run(&self) -> Option<f64>;
 
     /// A hook for giving a chance to the loaded program to release its resources.
     fn cleanup(&self);
+
+    /// Unload the shared library, its symbols must not be used afterward.
+    ///
+    /// This is equivalent to dropping the program, which closes the library with `dlclose`.
+    fn unload(self)
+    where
+        Self: Sized,
+    {
+        drop(self)
+    }
+}
+
+/// The reasons a program can fail to load.
+#[derive(Debug)]
+pub enum LoadError {
+    /// There is no file at the given path.
+    NotFound(String),
+    /// The library does not export one of the functions of the interface.
+    MissingSymbol(&'static str),
+    /// The library could not be opened, for instance because it is not a valid shared object.
+    Dlopen(dlopen::Error),
+    /// The library targets another version of the ABI.
+    AbiVersion(u32),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotFound(path) => write!(f, "file '{}' not found", path),
+            LoadError::MissingSymbol(symbol) => write!(f, "missing symbol '{}'", symbol),
+            LoadError::Dlopen(err) => write!(f, "dlopen failed: {}", err),
+            LoadError::AbiVersion(version) => write!(
+                f,
+                "the library targets ABI version {}, but bench supports version {}",
+                version, ABI_VERSION
+            ),
+        }
+    }
+}
+
+/// Load the library at `path` and its interface.
+fn load_container<T: WrapperApi>(path: &OsStr) -> Result<Container<T>, LoadError> {
+    if !Path::new(path).exists() {
+        return Err(LoadError::NotFound(path.to_string_lossy().into_owned()));
+    }
+    unsafe { Container::<T>::load(path) }.map_err(LoadError::Dlopen)
+}
+
+/// Ensure that the library exports one of the two run functions.
+fn check_run<R, S>(run: &Option<R>, run_result: &Option<S>) -> Result<(), LoadError> {
+    if run.is_none() && run_result.is_none() {
+        Err(LoadError::MissingSymbol("run"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Ensure that the library targets the ABI version supported by bench.
+fn check_abi_version(version: &Option<AbiVersion>) -> Result<(), LoadError> {
+    let version = match version {
+        Some(version) => unsafe { version.bench_abi_version() },
+        None => 0,
+    };
+    if version == ABI_VERSION {
+        Ok(())
+    } else {
+        Err(LoadError::AbiVersion(version))
+    }
 }
 
 // —————————————————————————— Language interfaces ——————————————————————————— //
 
+// Only `run` (or `run_result`, that returns a metric) is mandatory, `init` and `cleanup` are
+// skipped if the library does not export them.
+
 #[derive(WrapperApi)]
+struct AbiVersion {
+    bench_abi_version: unsafe extern "C" fn() -> u32,
+}
+
+#[derive(WrapperMultiApi)]
 struct CBenchmarkAPI {
+    abi_version: Option<AbiVersion>,
+    init: Option<CInit>,
+    run: Option<CRun>,
+    run_result: Option<CRunResult>,
+    cleanup: Option<CCleanup>,
+}
+
+#[derive(WrapperApi)]
+struct CInit {
     init: unsafe extern "C" fn(argc: c_int, argv: *const *const c_char),
+}
+
+#[derive(WrapperApi)]
+struct CRun {
     run: unsafe extern "C" fn(),
-    cleanup: unsafe extern "C" fn(),
 }
 
 #[derive(WrapperApi)]
+struct CRunResult {
+    run_result: unsafe extern "C" fn() -> f64,
+}
+
+#[derive(WrapperApi)]
+struct CCleanup {
+    cleanup: unsafe extern "C" fn(),
+}
+
+#[derive(WrapperMultiApi)]
 struct RustBenchmarkApi {
+    abi_version: Option<AbiVersion>,
+    init: Option<RustInit>,
+    run: Option<RustRun>,
+    run_result: Option<RustRunResult>,
+    cleanup: Option<RustCleanup>,
+}
+
+#[derive(WrapperApi)]
+struct RustInit {
     init_rust: unsafe fn(args: &Vec<OsString>),
+}
+
+#[derive(WrapperApi)]
+struct RustRun {
     run: unsafe fn(),
+}
+
+#[derive(WrapperApi)]
+struct RustRunResult {
+    run_result: unsafe fn() -> f64,
+}
+
+#[derive(WrapperApi)]
+struct RustCleanup {
     cleanup: unsafe fn(),
 }
 
@@ -51,29 +181,40 @@ pub struct RustProgram {
 // ————————————————————————————— C Implementation ——————————————————————————— //
 
 impl ExternalProgram for CProgram {
-    fn load<P: AsRef<OsStr>>(path: P) -> Self {
-        let program = unsafe {
-            Container::<CBenchmarkAPI>::load(path).expect("Could not load dynamic library")
-        };
-        Self { program }
+    fn load<P: AsRef<OsStr>>(path: P) -> Result<Self, LoadError> {
+        let program: Container<CBenchmarkAPI> = load_container(path.as_ref())?;
+        check_abi_version(&program.abi_version)?;
+        check_run(&program.run, &program.run_result)?;
+        Ok(Self { program })
     }
 
     fn init(&self, args: &str) {
-        let c_args = CArgs::new(args);
-        unsafe {
-            self.program.init(c_args.argc(), c_args.argv());
+        if let Some(init) = &self.program.init {
+            let c_args = CArgs::new(args);
+            unsafe {
+                init.init(c_args.argc(), c_args.argv());
+            }
         }
     }
 
-    fn run(&self) {
+    fn run(&self) -> Option<f64> {
         unsafe {
-            self.program.run();
+            match (&self.program.run, &self.program.run_result) {
+                (_, Some(run)) => Some(run.run_result()),
+                (Some(run), None) => {
+                    run.run();
+                    None
+                }
+                (None, None) => unreachable!("checked when loading"),
+            }
         }
     }
 
     fn cleanup(&self) {
-        unsafe {
-            self.program.cleanup();
+        if let Some(cleanup) = &self.program.cleanup {
+            unsafe {
+                cleanup.cleanup();
+            }
         }
     }
 }
@@ -81,29 +222,100 @@ impl ExternalProgram for CProgram {
 // ——————————————————————————— Rust Implementation —————————————————————————— //
 
 impl ExternalProgram for RustProgram {
-    fn load<P: AsRef<OsStr>>(path: P) -> Self {
-        let program = unsafe {
-            Container::<RustBenchmarkApi>::load(path).expect("Could not load dynamic library")
-        };
-        Self { program }
+    fn load<P: AsRef<OsStr>>(path: P) -> Result<Self, LoadError> {
+        let program: Container<RustBenchmarkApi> = load_container(path.as_ref())?;
+        check_abi_version(&program.abi_version)?;
+        check_run(&program.run, &program.run_result)?;
+        Ok(Self { program })
     }
 
     fn init(&self, args: &str) {
-        let rust_args = RustArgs::new(args);
-        unsafe {
-            self.program.init_rust(rust_args.args());
+        if let Some(init) = &self.program.init {
+            let rust_args = RustArgs::new(args);
+            unsafe {
+                init.init_rust(rust_args.args());
+            }
         }
     }
 
-    fn run(&self) {
+    fn run(&self) -> Option<f64> {
         unsafe {
-            self.program.run();
+            match (&self.program.run, &self.program.run_result) {
+                (_, Some(run)) => Some(run.run_result()),
+                (Some(run), None) => {
+                    run.run();
+                    None
+                }
+                (None, None) => unreachable!("checked when loading"),
+            }
         }
     }
 
     fn cleanup(&self) {
-        unsafe {
-            self.program.cleanup();
+        if let Some(cleanup) = &self.program.cleanup {
+            unsafe {
+                cleanup.cleanup();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build/mm.so");
+    const MISSING_RUN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build/missing_run.so");
+    const RUN_ONLY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build/run_only.so");
+    const ABI_99_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build/abi_99.so");
+    const RUN_RESULT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build/run_result.so");
+
+    #[test]
+    fn load_after_unload() {
+        for _ in 0..2 {
+            let program = CProgram::load(MM_PATH).unwrap();
+            program.init("16");
+            program.run();
+            program.cleanup();
+            program.unload();
+        }
+    }
+
+    #[test]
+    fn optional_init_and_cleanup() {
+        let program = CProgram::load(RUN_ONLY_PATH).unwrap();
+        program.init("");
+        assert_eq!(program.run(), None);
+        program.cleanup();
+    }
+
+    #[test]
+    fn run_with_result() {
+        let program = CProgram::load(RUN_RESULT_PATH).unwrap();
+        assert_eq!(program.run(), Some(42.));
+    }
+
+    #[test]
+    fn abi_version_mismatch() {
+        assert!(matches!(
+            CProgram::load(ABI_99_PATH),
+            Err(LoadError::AbiVersion(99))
+        ));
+    }
+
+    #[test]
+    fn load_errors() {
+        match CProgram::load(MISSING_RUN_PATH) {
+            Err(err @ LoadError::MissingSymbol(_)) => assert!(err.to_string().contains("'run'")),
+            _ => panic!("expected a missing symbol error"),
         }
+        assert!(matches!(
+            CProgram::load("build/does_not_exist.so"),
+            Err(LoadError::NotFound(_))
+        ));
+        assert!(matches!(
+            CProgram::load(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")),
+            Err(LoadError::Dlopen(_))
+        ));
     }
 }
@@ -0,0 +1,155 @@
+//! Raw events
+//!
+//! Counters for events that are not exposed by the `perf_event` crate, such as
+//! microarchitecture-specific events, configured directly with a perf type and config.
+use std::str::FromStr;
+#[cfg(target_os = "linux")]
+use {
+    perf_event::CountAndTime,
+    perf_event_open_sys::bindings::{self, perf_event_attr},
+    perf_event_open_sys::{ioctls, perf_event_open},
+    std::fs::File,
+    std::io::{self, Read},
+    std::os::unix::io::{AsRawFd, FromRawFd},
+};
+
+/// A raw perf event, as passed to the `--raw-event` flag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawEvent {
+    /// The name under which the event is reported.
+    pub label: String,
+    /// The perf event type, e.g. 4 for `PERF_TYPE_RAW`.
+    pub type_: u32,
+    /// The event configuration, its meaning depends on the type.
+    pub config: u64,
+}
+
+impl FromStr for RawEvent {
+    type Err = String;
+
+    /// Parse a `[LABEL=]TYPE:CONFIG` definition, where type and config are hexadecimal numbers.
+    ///
+    /// The label defaults to `TYPE:CONFIG`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (label, event) = match s.find('=') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => (s, s),
+        };
+        let error = || format!("expected [LABEL=]TYPE:CONFIG, got '{}'", s);
+        let idx = event.find(':').ok_or_else(error)?;
+        let type_ = u32::from_str_radix(trim_hex_prefix(&event[..idx]), 16).map_err(|_| error())?;
+        let config =
+            u64::from_str_radix(trim_hex_prefix(&event[idx + 1..]), 16).map_err(|_| error())?;
+        if label.is_empty() {
+            return Err(error());
+        }
+        Ok(Self {
+            label: label.to_string(),
+            type_,
+            config,
+        })
+    }
+}
+
+fn trim_hex_prefix(number: &str) -> &str {
+    number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+        .unwrap_or(number)
+}
+
+/// A counter for a raw event, configured like the counters of the `perf_event` crate: counting
+/// starts disabled, only user space is counted and child processes are included.
+#[cfg(target_os = "linux")]
+pub struct RawCounter {
+    file: File,
+}
+
+#[cfg(target_os = "linux")]
+impl RawCounter {
+    pub fn new(event: &RawEvent) -> io::Result<Self> {
+        let mut attrs = perf_event_attr {
+            size: std::mem::size_of::<perf_event_attr>() as u32,
+            type_: event.type_,
+            config: event.config,
+            read_format: (bindings::perf_event_read_format_PERF_FORMAT_TOTAL_TIME_ENABLED
+                | bindings::perf_event_read_format_PERF_FORMAT_TOTAL_TIME_RUNNING)
+                as u64,
+            ..perf_event_attr::default()
+        };
+        attrs.set_disabled(1);
+        attrs.set_exclude_kernel(1);
+        attrs.set_exclude_hv(1);
+        attrs.set_inherit(1);
+
+        // SAFETY: `attrs` is a valid and fully initialized `perf_event_attr`, and the returned
+        // file descriptor is owned by nobody else.
+        unsafe {
+            let fd = perf_event_open(&mut attrs, 0, -1, -1, 0);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                file: File::from_raw_fd(fd),
+            })
+        }
+    }
+
+    pub fn enable(&mut self) -> io::Result<()> {
+        // SAFETY: the file descriptor is a valid perf event.
+        check_ioctl(unsafe { ioctls::ENABLE(self.file.as_raw_fd(), 0) })
+    }
+
+    pub fn reset(&mut self) -> io::Result<()> {
+        // SAFETY: the file descriptor is a valid perf event.
+        check_ioctl(unsafe { ioctls::RESET(self.file.as_raw_fd(), 0) })
+    }
+
+    pub fn disable(&mut self) -> io::Result<()> {
+        // SAFETY: the file descriptor is a valid perf event.
+        check_ioctl(unsafe { ioctls::DISABLE(self.file.as_raw_fd(), 0) })
+    }
+
+    pub fn read_count_and_time(&mut self) -> io::Result<CountAndTime> {
+        let mut buf = [0_u8; 24];
+        self.file.read_exact(&mut buf)?;
+        let value = |idx: usize| {
+            let mut bytes = [0_u8; 8];
+            bytes.copy_from_slice(&buf[idx * 8..(idx + 1) * 8]);
+            u64::from_ne_bytes(bytes)
+        };
+        Ok(CountAndTime {
+            count: value(0),
+            time_enabled: value(1),
+            time_running: value(2),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_ioctl(result: i32) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_raw_event() {
+        let event: RawEvent = "stalls=0x4:0x1a2".parse().unwrap();
+        assert_eq!(event.label, "stalls");
+        assert_eq!(event.type_, 4);
+        assert_eq!(event.config, 0x1a2);
+
+        let event: RawEvent = "1:2".parse().unwrap();
+        assert_eq!(event.label, "1:2");
+        assert!("stalls=0x4".parse::<RawEvent>().is_err());
+        assert!("=4:2".parse::<RawEvent>().is_err());
+        assert!("4:xyz".parse::<RawEvent>().is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Report
+//!
+//! The JSON document printed by bench: the results, along with optional metadata. The report can
+//! also be rendered as a self-contained HTML page.
+use crate::sysinfo::SystemInfo;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the JSON format, to be bumped whenever fields are added, removed or renamed.
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+pub struct Report<'a, T> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<SystemInfo>,
+    #[serde(flatten)]
+    results: &'a T,
+}
+
+impl<'a, T: Serialize> Report<'a, T> {
+    pub fn new(results: &'a T, system: Option<SystemInfo>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            system,
+            results,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Information about the host, if collected.
+    pub fn system(&self) -> Option<&SystemInfo> {
+        self.system.as_ref()
+    }
+
+    /// Render the report as an HTML page, with a bar chart of the execution times followed by
+    /// the content of the JSON report.
+    ///
+    /// The page has no external dependency, the chart is an inline SVG.
+    pub fn html(&self) -> String {
+        let report = serde_json::to_value(self).unwrap();
+        let mut times = Vec::new();
+        collect_execution_times(&report, "", &mut times);
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>bench report</title>\n\
+             <style>{}</style>\n</head>\n<body>\n<h1>bench report</h1>\n{}\n{}\n</body>\n</html>\n",
+            HTML_STYLE,
+            bar_chart(&times),
+            html_value(&report)
+        )
+    }
+}
+
+// —————————————————————————————————— HTML —————————————————————————————————— //
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+    table { border-collapse: collapse; margin: 0.5em 0; } \
+    th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; vertical-align: top; }";
+
+/// Width of the chart, in pixels.
+const CHART_WIDTH: f64 = 600.;
+/// Width reserved for the labels of the chart, in pixels.
+const CHART_LABEL_WIDTH: f64 = 200.;
+/// Height of a bar of the chart, including the spacing, in pixels.
+const CHART_BAR_HEIGHT: f64 = 24.;
+
+/// Collect the execution time of each `Stats` of the report, labelled by the target, value or
+/// side of the comparison it belongs to.
+fn collect_execution_times(value: &Value, label: &str, times: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Object(fields) => {
+            // Only stats carry both fields, the deltas of a comparison do not have runs
+            if let (Some(time), true) = (
+                fields.get("execution_time").and_then(Value::as_f64),
+                fields.contains_key("runs"),
+            ) {
+                let label = if label.is_empty() {
+                    "execution time"
+                } else {
+                    label
+                };
+                times.push((label.to_string(), time));
+                return;
+            }
+            for (key, field) in fields {
+                let label = match (label, key.as_str()) {
+                    (_, "stats") | (_, "system") => label.to_string(),
+                    ("", key) => key.to_string(),
+                    (label, key) => format!("{} {}", label, key),
+                };
+                collect_execution_times(field, &label, times);
+            }
+        }
+        Value::Array(items) => {
+            for (idx, item) in items.iter().enumerate() {
+                let label = match (&item["target"], &item["value"]) {
+                    (Value::Object(target), _) => format!(
+                        "{} {}",
+                        target["path"].as_str().unwrap_or_default(),
+                        target["args"].as_str().unwrap_or_default()
+                    )
+                    .trim()
+                    .to_string(),
+                    (_, Value::String(value)) => value.clone(),
+                    _ => format!("{}[{}]", label, idx),
+                };
+                collect_execution_times(item, &label, times);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Render the execution times as a horizontal bar chart.
+fn bar_chart(times: &[(String, f64)]) -> String {
+    let max = times.iter().map(|(_, time)| *time).fold(0., f64::max);
+    let height = CHART_BAR_HEIGHT * times.len() as f64;
+    let mut svg = format!(
+        "<h2>Execution time (s)</h2>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        CHART_WIDTH, height
+    );
+    for (idx, (label, time)) in times.iter().enumerate() {
+        let y = idx as f64 * CHART_BAR_HEIGHT;
+        let width = if max > 0. {
+            time / max * (CHART_WIDTH - CHART_LABEL_WIDTH - 60.)
+        } else {
+            0.
+        };
+        svg.push_str(&format!(
+            "\n<text x=\"0\" y=\"{}\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#4878a8\"/>\
+             <text x=\"{:.1}\" y=\"{}\">{:.2}</text>",
+            y + 16.,
+            escape(label),
+            CHART_LABEL_WIDTH,
+            y + 4.,
+            width,
+            CHART_BAR_HEIGHT - 8.,
+            CHART_LABEL_WIDTH + width + 4.,
+            y + 16.,
+            time
+        ));
+    }
+    svg.push_str("\n</svg>");
+    svg
+}
+
+/// Render a JSON value, objects are rendered as tables with one row per field.
+fn html_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("n/a"),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => escape(value),
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| format!("<li>{}</li>", html_value(item)))
+                .collect();
+            format!("<ol>{}</ol>", items.join(""))
+        }
+        Value::Object(fields) => {
+            let rows: Vec<String> = fields
+                .iter()
+                .map(|(key, field)| {
+                    format!(
+                        "<tr><th>{}</th><td>{}</td></tr>",
+                        escape(key),
+                        html_value(field)
+                    )
+                })
+                .collect();
+            format!("<table>\n{}\n</table>", rows.join("\n"))
+        }
+    }
+}
+
+/// Escape the characters with a special meaning in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::Counters;
+    use crate::stats::Stats;
+    use crate::suite::{Suite, Target};
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn system_info_in_json() {
+        let stats = Stats::new(Counters::default());
+        let report = Report::new(&stats, Some(SystemInfo::collect()));
+        let json: serde_json::Value = serde_json::from_str(&report.json()).unwrap();
+
+        assert!(!json["system"]["cpu_model"].as_str().unwrap().is_empty());
+        assert!(json["execution_time"].is_number());
+    }
+
+    #[test]
+    fn schema_version_in_json() {
+        let stats = Stats::new(Counters::default());
+        let json = Report::new(&stats, None).json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+
+        // The report can still be loaded as a baseline
+        assert!(Stats::from_json(&json).is_ok());
+    }
+
+    #[test]
+    fn suite_chart() {
+        let results = ["fast.so", "slow.so <2>"]
+            .iter()
+            .zip(&[1_000_000_000, 2_000_000_000])
+            .map(|(path, &wall_clock)| {
+                let target = Target {
+                    path: path.to_string(),
+                    args: String::from("-n 4"),
+                };
+                let stats = Stats::new(Counters {
+                    wall_clock,
+                    ..Counters::default()
+                });
+                (target, stats)
+            })
+            .collect();
+        let suite = Suite::new(results);
+        let html = Report::new(&suite, None).html();
+
+        // One bar per target, the slowest spans the whole chart
+        assert_eq!(html.matches("<rect").count(), 2);
+        assert!(html.contains(">fast.so -n 4</text>"));
+        assert!(html.contains(">slow.so &lt;2&gt; -n 4</text>"));
+        assert!(html.contains("geomean_execution_time"));
+    }
+}
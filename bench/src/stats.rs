@@ -1,41 +1,1150 @@
-use crate::monitor::Counters;
-use serde::Serialize;
-use serde_json;
+use crate::command::RunStatus;
+use crate::monitor::{CoreCounts, Counters};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Serialize)]
+/// Size of a cache line in bytes, used to estimate the memory bandwidth.
+const CACHE_LINE_SIZE: u64 = 64;
+
+/// Tolerated deviation of the frequency from the reference frequency, above which the frequency is
+/// considered unstable.
+const FREQUENCY_TOLERANCE: f64 = 0.05;
+
+/// Parallel efficiency below which a warning is emitted, as the threads spend most of their time
+/// waiting.
+const LOW_PARALLEL_EFFICIENCY: f64 = 0.5;
+
+/// Statistics derived from the raw counters.
+///
+/// Metrics that can not be derived from the monitored counters, or whose denominator is zero, are
+/// `None`. They are serialized as `null` and displayed as `n/a`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Stats {
-    cycles: u64,
-    cpu_usage: f64,
-    instr_per_cycle: f64,
-    cache_miss_rate: f64,
+    cycles: Option<u64>,
+    instructions: Option<u64>,
+    cpu_usage: Option<f64>,
+    instr_per_cycle: Option<f64>,
+    cycles_per_instr: Option<f64>,
+    /// Fraction of the cycles stalled in the frontend (fetch and decode).
+    frontend_stall_rate: Option<f64>,
+    /// Fraction of the cycles stalled in the backend (execution and memory).
+    backend_stall_rate: Option<f64>,
+    cache_miss_rate: Option<f64>,
+    /// Estimated memory bandwidth, in GB/s.
+    ///
+    /// Assumes that each last level cache miss transfers one cache line of `CACHE_LINE_SIZE`
+    /// bytes from memory, ignoring prefetching and write-backs.
+    memory_bandwidth_gbs: Option<f64>,
+    execution_time: f64, // in seconds
+    frequency_scaling: Option<f64>,
+    /// Whether the frequency stayed within `FREQUENCY_TOLERANCE` of the reference frequency, i.e.
+    /// whether turbo boost or frequency scaling did not affect the measurement.
+    frequency_stable: Option<bool>,
+    page_faults: Option<u64>,
+    context_switches: Option<u64>,
+    dtlb_misses: Option<u64>,
+    itlb_misses: Option<u64>,
+    /// Data TLB misses per instruction, high rates reveal programs bound by address translation.
+    dtlb_misses_per_instr: Option<f64>,
+    peak_rss_kb: Option<u64>,
+    /// Number of threads observed while running the program.
+    threads: Option<u64>,
+    /// The task clock divided by the wall clock time, i.e. the average number of busy CPUs. This is
+    /// the same as the CPU usage.
+    parallelism: Option<f64>,
+    /// The parallelism divided by the number of threads, 1 when all the threads were busy during
+    /// the whole run.
+    ///
+    /// The number of threads is the one given with `with_threads`, or the number of threads
+    /// observed otherwise.
+    parallel_efficiency: Option<f64>,
+    #[serde(default)]
+    raw_events: BTreeMap<String, u64>,
+    /// Metric reported by the benchmarked program, averaged over all runs.
+    target_metric: Option<f64>,
+    /// Number of runs the counts are averaged over.
+    #[serde(default = "one_run")]
+    runs: usize,
+    /// Breakdown of the counts per core, when requested.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    per_core: Vec<CoreCounts>,
+    /// Standard output of the last run, when captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stdout: Option<String>,
+    /// How the program ended in executable mode: the first failed run, or the last run if none
+    /// failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<RunStatus>,
+    /// Counts of each sampling interval of the last run, when sampled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    time_series: Vec<Counters>,
+    /// Percentiles of the execution time of the individual runs, in seconds, with several runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    execution_time_percentiles: Option<Percentiles>,
+    /// Percentiles of the cycles of the individual runs, with several runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cycles_percentiles: Option<Percentiles>,
+    /// Measurements of the individual runs, in order.
+    #[serde(skip)]
+    samples: Vec<Sample>,
+}
+
+/// The median and tail of the measurements of the individual runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl Percentiles {
+    /// Compute the percentiles of `values` with the nearest-rank method, or return `None` if there
+    /// is no value.
+    ///
+    /// With few values the ranks are clamped to the slowest value, e.g. the p95 and p99 of 10
+    /// values are both the largest one.
+    pub fn new(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: f64| {
+            let rank = (p / 100. * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Some(Self {
+            p50: percentile(50.),
+            p95: percentile(95.),
+            p99: percentile(99.),
+        })
+    }
+}
+
+/// The measurement of a single run.
+#[derive(Debug, PartialEq)]
+pub struct Sample {
+    cycles: Option<u64>,
+    instructions: Option<u64>,
     execution_time: f64, // in seconds
-    frequency_scaling: f64,
+    target_metric: Option<f64>,
+}
+
+impl Sample {
+    pub fn new(counters: &Counters) -> Self {
+        Self {
+            cycles: counters.cycles,
+            instructions: counters.instructions,
+            execution_time: counters.wall_clock as f64 / 1_000_000_000.,
+            target_metric: counters.target_metric,
+        }
+    }
 }
 
 impl Stats {
     pub fn new(counters: Counters) -> Self {
+        let frequency_scaling = ratio(counters.cycles, counters.ref_cycles);
         Self {
             cycles: counters.cycles,
-            instr_per_cycle: counters.instructions as f64 / counters.cycles as f64,
-            cpu_usage: counters.task_clock as f64 / counters.wall_clock as f64,
-            cache_miss_rate: counters.cache_misses as f64 / counters.cache_references as f64,
+            instructions: counters.instructions,
+            instr_per_cycle: ratio(counters.instructions, counters.cycles),
+            cycles_per_instr: ratio(counters.cycles, counters.instructions),
+            frontend_stall_rate: ratio(counters.stalled_cycles_frontend, counters.cycles),
+            backend_stall_rate: ratio(counters.stalled_cycles_backend, counters.cycles),
+            cpu_usage: ratio(counters.task_clock, Some(counters.wall_clock)),
+            cache_miss_rate: ratio(counters.cache_misses, counters.cache_references),
+            // Bytes per nanosecond are GB/s
+            memory_bandwidth_gbs: ratio(
+                counters
+                    .cache_misses
+                    .map(|misses| misses.saturating_mul(CACHE_LINE_SIZE)),
+                Some(counters.wall_clock),
+            ),
             execution_time: counters.wall_clock as f64 / 1_000_000_000.,
-            frequency_scaling: counters.cycles as f64 / counters.ref_cycles as f64,
+            frequency_scaling,
+            frequency_stable: frequency_scaling
+                .map(|scaling| (scaling - 1.).abs() <= FREQUENCY_TOLERANCE),
+            page_faults: counters.page_faults,
+            context_switches: counters.context_switches,
+            dtlb_misses: counters.dtlb_misses,
+            itlb_misses: counters.itlb_misses,
+            dtlb_misses_per_instr: ratio(counters.dtlb_misses, counters.instructions),
+            peak_rss_kb: counters.peak_rss_kb,
+            threads: counters.threads,
+            parallelism: ratio(counters.task_clock, Some(counters.wall_clock)),
+            parallel_efficiency: ratio(
+                counters.task_clock,
+                counters
+                    .threads
+                    .map(|threads| threads.saturating_mul(counters.wall_clock)),
+            ),
+            raw_events: counters.raw.into_iter().collect(),
+            target_metric: counters.target_metric,
+            runs: 1,
+            per_core: counters.per_core,
+            stdout: None,
+            status: None,
+            time_series: counters.time_series,
+            execution_time_percentiles: None,
+            cycles_percentiles: None,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Record the measurements of the individual runs, and their percentiles if there are several
+    /// runs.
+    ///
+    /// The percentiles of the cycles are only computed if all the runs collected them.
+    pub fn with_samples(mut self, samples: Vec<Sample>) -> Self {
+        if samples.len() > 1 {
+            let times: Vec<f64> = samples.iter().map(|s| s.execution_time).collect();
+            let cycles: Option<Vec<f64>> = samples.iter().map(|s| as_f64(s.cycles)).collect();
+            self.execution_time_percentiles = Percentiles::new(&times);
+            self.cycles_percentiles = cycles.and_then(|cycles| Percentiles::new(&cycles));
+        }
+        self.samples = samples;
+        self
+    }
+
+    /// Load stats from their JSON representation, metrics missing from the JSON are `None`.
+    pub fn from_json(json: &str) -> Result<Stats, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Record the number of runs the counters have been averaged over.
+    pub fn with_runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    /// Record the standard output of the program.
+    pub fn with_stdout(mut self, stdout: Option<String>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// Record how the program ended.
+    pub fn with_status(mut self, status: Option<RunStatus>) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Compute the parallel efficiency from the number of threads used by the program, if given,
+    /// instead of the number of threads observed.
+    pub fn with_threads(mut self, threads: Option<u64>) -> Self {
+        if let Some(threads) = threads.filter(|&threads| threads > 0) {
+            self.parallel_efficiency = self.parallelism.map(|p| p / threads as f64);
         }
+        self
+    }
+
+    /// Record the time series of the program.
+    pub fn with_time_series(mut self, time_series: Vec<Counters>) -> Self {
+        self.time_series = time_series;
+        self
     }
 
-    pub fn json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    /// Format the stats as a markdown table, with one column per metric.
+    pub fn markdown(&self) -> String {
+        let metrics = self.metrics();
+        let mut header: Vec<String> = metrics.iter().map(|m| m.name.to_string()).collect();
+        let mut values: Vec<String> = metrics.iter().map(Metric::format).collect();
+        for (label, count) in &self.raw_events {
+            header.push(label.clone());
+            values.push(count.to_string());
+        }
+        markdown_table(&header, &[values])
     }
+
+    /// Format the individual runs as whitespace-separated columns, preceded by a commented header
+    /// that gnuplot ignores. Missing values are reported as `NaN`.
+    pub fn gnuplot(&self) -> String {
+        let mut lines = vec![String::from(
+            "# run cycles instructions execution_time target_metric",
+        )];
+        let column = |value: Option<f64>| match value {
+            Some(value) => value.to_string(),
+            None => String::from("NaN"),
+        };
+        for (idx, sample) in self.samples.iter().enumerate() {
+            lines.push(format!(
+                "{} {} {} {} {}",
+                idx,
+                column(as_f64(sample.cycles)),
+                column(as_f64(sample.instructions)),
+                sample.execution_time,
+                column(sample.target_metric),
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Format the percentiles of the execution time and cycles across runs, or `n/a` if there are
+    /// not enough runs.
+    pub fn percentiles(&self) -> String {
+        let time = |value: f64| format!("{:.4}s", value);
+        let cycles = |value: f64| format_count(value as u64);
+        let line =
+            |name: &str, percentiles: Option<Percentiles>, format: &dyn Fn(f64) -> String| {
+                match percentiles {
+                    Some(p) => format!(
+                        "{}\tp50 {}\tp95 {}\tp99 {}",
+                        name,
+                        format(p.p50),
+                        format(p.p95),
+                        format(p.p99)
+                    ),
+                    None => format!("{}\tn/a", name),
+                }
+            };
+        format!(
+            "{}\n{}",
+            line("exec time:", self.execution_time_percentiles, &time),
+            line("cycles:\t", self.cycles_percentiles, &cycles)
+        )
+    }
+
+    /// Return a warning if the frequency deviated from the reference frequency during the
+    /// measurement, which makes the results depend on the thermal state of the machine.
+    pub fn frequency_warning(&self) -> Option<String> {
+        if self.frequency_stable? {
+            return None;
+        }
+        Some(format!(
+            "the CPU ran at {:.2} times its reference frequency, disable turbo boost or pin the CPU \
+             frequency for stable results",
+            self.frequency_scaling?
+        ))
+    }
+
+    /// Return a warning if the parallel efficiency is below `LOW_PARALLEL_EFFICIENCY`, i.e. if the
+    /// threads spent most of their time waiting rather than computing.
+    pub fn parallel_efficiency_warning(&self) -> Option<String> {
+        let efficiency = self.parallel_efficiency?;
+        if efficiency >= LOW_PARALLEL_EFFICIENCY {
+            return None;
+        }
+        Some(format!(
+            "the parallel efficiency is {:.2}%, the threads spent most of their time waiting",
+            efficiency * 100.
+        ))
+    }
+
+    /// Execution time, in seconds.
+    pub fn execution_time(&self) -> f64 {
+        self.execution_time
+    }
+
+    /// Compute the relative change of each metric from `self` to `other`.
+    pub fn delta(&self, other: &Stats) -> StatsDelta {
+        StatsDelta {
+            cycles: relative_change(as_f64(self.cycles), as_f64(other.cycles)),
+            instructions: relative_change(as_f64(self.instructions), as_f64(other.instructions)),
+            cpu_usage: relative_change(self.cpu_usage, other.cpu_usage),
+            instr_per_cycle: relative_change(self.instr_per_cycle, other.instr_per_cycle),
+            cycles_per_instr: relative_change(self.cycles_per_instr, other.cycles_per_instr),
+            frontend_stall_rate: relative_change(
+                self.frontend_stall_rate,
+                other.frontend_stall_rate,
+            ),
+            backend_stall_rate: relative_change(self.backend_stall_rate, other.backend_stall_rate),
+            cache_miss_rate: relative_change(self.cache_miss_rate, other.cache_miss_rate),
+            memory_bandwidth_gbs: relative_change(
+                self.memory_bandwidth_gbs,
+                other.memory_bandwidth_gbs,
+            ),
+            execution_time: relative_change(Some(self.execution_time), Some(other.execution_time)),
+            frequency_scaling: relative_change(self.frequency_scaling, other.frequency_scaling),
+            page_faults: relative_change(as_f64(self.page_faults), as_f64(other.page_faults)),
+            context_switches: relative_change(
+                as_f64(self.context_switches),
+                as_f64(other.context_switches),
+            ),
+            dtlb_misses: relative_change(as_f64(self.dtlb_misses), as_f64(other.dtlb_misses)),
+            itlb_misses: relative_change(as_f64(self.itlb_misses), as_f64(other.itlb_misses)),
+            dtlb_misses_per_instr: relative_change(
+                self.dtlb_misses_per_instr,
+                other.dtlb_misses_per_instr,
+            ),
+            peak_rss_kb: relative_change(as_f64(self.peak_rss_kb), as_f64(other.peak_rss_kb)),
+            threads: relative_change(as_f64(self.threads), as_f64(other.threads)),
+            parallel_efficiency: relative_change(
+                self.parallel_efficiency,
+                other.parallel_efficiency,
+            ),
+        }
+    }
+
+    /// The metrics, in the same order as the `Display` implementation.
+    pub fn metrics(&self) -> Vec<Metric> {
+        vec![
+            Metric::new("cycles", as_f64(self.cycles), 0),
+            Metric::new("instructions", as_f64(self.instructions), 0),
+            Metric::new("freq/max freq", self.frequency_scaling, 2),
+            Metric::new("instr/cycles", self.instr_per_cycle, 2),
+            Metric::new("cycles/instr", self.cycles_per_instr, 2),
+            Metric::new(
+                "frontend stall (%)",
+                self.frontend_stall_rate.map(|r| r * 100.),
+                2,
+            ),
+            Metric::new(
+                "backend stall (%)",
+                self.backend_stall_rate.map(|r| r * 100.),
+                2,
+            ),
+            Metric::new("cpu usage", self.cpu_usage, 2),
+            Metric::new("cache miss (%)", self.cache_miss_rate.map(|r| r * 100.), 2),
+            Metric::new("mem bw (GB/s)", self.memory_bandwidth_gbs, 2),
+            Metric::new("page faults", as_f64(self.page_faults), 0),
+            Metric::new("ctx switches", as_f64(self.context_switches), 0),
+            Metric::new("dTLB misses", as_f64(self.dtlb_misses), 0),
+            Metric::new("iTLB misses", as_f64(self.itlb_misses), 0),
+            Metric::new("dTLB miss/instr", self.dtlb_misses_per_instr, 4),
+            Metric::new("peak rss (KB)", as_f64(self.peak_rss_kb), 0),
+            Metric::new("threads", as_f64(self.threads), 0),
+            Metric::new(
+                "parallel eff. (%)",
+                self.parallel_efficiency.map(|e| e * 100.),
+                2,
+            ),
+            Metric::new("exec time (s)", Some(self.execution_time), 2),
+        ]
+    }
+}
+
+/// Return `numerator / denominator`, or `None` if one of them has not been measured or if the
+/// denominator is zero.
+fn ratio(numerator: Option<u64>, denominator: Option<u64>) -> Option<f64> {
+    match denominator? {
+        0 => None,
+        denominator => Some(numerator? as f64 / denominator as f64),
+    }
+}
+
+/// Return the sample standard deviation of `values` divided by their mean, or `None` if there are
+/// less than two values or if the mean is zero.
+pub fn coefficient_of_variation(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if mean == 0. {
+        return None;
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.);
+    Some(variance.sqrt() / mean.abs())
+}
+
+fn one_run() -> usize {
+    1
+}
+
+fn as_f64(count: Option<u64>) -> Option<f64> {
+    count.map(|count| count as f64)
 }
 
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "cycles:\t\t{}\nfreq/max freq:\t{:.2}\ninstr/cycles:\t{:.2}\ncpu usage:\t{:.2}\ncache miss:\t{:.2}%\nexec time:\t{:.2}s",
-            self.cycles, self.frequency_scaling,self.instr_per_cycle, self.cpu_usage, self.cache_miss_rate * 100.,self.execution_time
-        )
+            "cycles:\t\t{}\ninstructions:\t{}\nfreq/max freq:\t{}\ninstr/cycles:\t{}\ncycles/instr:\t{}\nfrontend stall:\t{}\nbackend stall:\t{}\ncpu usage:\t{}\ncache miss:\t{}\nmem bw:\t\t{}\npage faults:\t{}\nctx switches:\t{}\ndTLB misses:\t{}\niTLB misses:\t{}\ndTLB miss rate:\t{}\npeak rss:\t{}\nthreads:\t{}\nparallel eff.:\t{}\nexec time:\t{:.2}s",
+            display_count(self.cycles),
+            display_count(self.instructions),
+            display(self.frequency_scaling, 2, ""),
+            display(self.instr_per_cycle, 2, ""),
+            display(self.cycles_per_instr, 2, ""),
+            display(self.frontend_stall_rate.map(|r| r * 100.), 2, "%"),
+            display(self.backend_stall_rate.map(|r| r * 100.), 2, "%"),
+            display(self.cpu_usage, 2, ""),
+            display(self.cache_miss_rate.map(|r| r * 100.), 2, "%"),
+            display(self.memory_bandwidth_gbs, 2, " GB/s"),
+            display_count(self.page_faults),
+            display_count(self.context_switches),
+            display_count(self.dtlb_misses),
+            display_count(self.itlb_misses),
+            display(self.dtlb_misses_per_instr, 4, ""),
+            display(as_f64(self.peak_rss_kb), 0, " KB"),
+            display_count(self.threads),
+            display(self.parallel_efficiency.map(|e| e * 100.), 2, "%"),
+            self.execution_time
+        )?;
+        for (label, count) in &self.raw_events {
+            write!(f, "\n{}:\t{}", label, format_count(*count))?;
+        }
+        if let Some(metric) = self.target_metric {
+            write!(f, "\ntarget metric:\t{}", metric)?;
+        }
+        if self.runs > 1 {
+            write!(f, "\nruns:\t\t{}", self.runs)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format an optional value with the given precision and unit, or `n/a` if it is missing.
+fn display(value: Option<f64>, precision: usize, unit: &str) -> String {
+    match value {
+        Some(value) => format!("{:.*}{}", precision, value, unit),
+        None => String::from("n/a"),
+    }
+}
+
+/// Format a count with an SI suffix and two decimals, e.g. `30.00 G`, counts below 1000 are
+/// formatted as is.
+fn format_count(count: u64) -> String {
+    const SUFFIXES: [&str; 4] = ["K", "M", "G", "T"];
+    let mut value = count as f64;
+    let mut suffix = None;
+    for candidate in &SUFFIXES {
+        if value < 1000. {
+            break;
+        }
+        value /= 1000.;
+        suffix = Some(candidate);
+    }
+    match suffix {
+        Some(suffix) => format!("{:.2} {}", value, suffix),
+        None => count.to_string(),
+    }
+}
+
+/// Format an optional count with `format_count`, or `n/a` if it is missing.
+fn display_count(count: Option<u64>) -> String {
+    match count {
+        Some(count) => format_count(count),
+        None => String::from("n/a"),
+    }
+}
+
+// —————————————————————————————— Aggregation ——————————————————————————————— //
+
+/// Average the counts of several runs.
+///
+/// A count is `None` if it is missing from all the runs. The peak resident set size is the
+/// maximum across runs rather than the average, as is the number of threads. The time series are
+/// not averaged.
+pub fn mean(runs: &[Counters]) -> Counters {
+    let mean = |count: fn(&Counters) -> Option<u64>| {
+        let counts: Vec<u64> = runs.iter().filter_map(count).collect();
+        if counts.is_empty() {
+            None
+        } else {
+            Some((counts.iter().map(|&c| c as u128).sum::<u128>() / counts.len() as u128) as u64)
+        }
+    };
+    let metrics: Vec<f64> = runs.iter().filter_map(|c| c.target_metric).collect();
+    let target_metric = if metrics.is_empty() {
+        None
+    } else {
+        Some(metrics.iter().sum::<f64>() / metrics.len() as f64)
+    };
+    let raw = match runs.first() {
+        Some(first) => first
+            .raw
+            .iter()
+            .enumerate()
+            .map(|(idx, (label, _))| {
+                let total: u128 = runs.iter().map(|run| run.raw[idx].1 as u128).sum();
+                (label.clone(), (total / runs.len() as u128) as u64)
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let per_core = match runs.first() {
+        Some(first) => first
+            .per_core
+            .iter()
+            .enumerate()
+            .map(|(idx, core)| {
+                let cores: Vec<&CoreCounts> = runs.iter().map(|run| &run.per_core[idx]).collect();
+                let mean = |count: fn(&CoreCounts) -> Option<u64>| {
+                    let counts: Vec<u64> = cores.iter().filter_map(|c| count(c)).collect();
+                    if counts.is_empty() {
+                        None
+                    } else {
+                        Some(
+                            (counts.iter().map(|&c| c as u128).sum::<u128>() / counts.len() as u128)
+                                as u64,
+                        )
+                    }
+                };
+                CoreCounts {
+                    cpu: core.cpu,
+                    cycles: mean(|c| c.cycles),
+                    instructions: mean(|c| c.instructions),
+                    task_clock: mean(|c| c.task_clock),
+                }
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    Counters {
+        cycles: mean(|c| c.cycles),
+        ref_cycles: mean(|c| c.ref_cycles),
+        instructions: mean(|c| c.instructions),
+        stalled_cycles_frontend: mean(|c| c.stalled_cycles_frontend),
+        stalled_cycles_backend: mean(|c| c.stalled_cycles_backend),
+        task_clock: mean(|c| c.task_clock),
+        wall_clock: mean(|c| Some(c.wall_clock)).unwrap_or_default(),
+        cache_misses: mean(|c| c.cache_misses),
+        cache_references: mean(|c| c.cache_references),
+        page_faults: mean(|c| c.page_faults),
+        context_switches: mean(|c| c.context_switches),
+        dtlb_misses: mean(|c| c.dtlb_misses),
+        itlb_misses: mean(|c| c.itlb_misses),
+        peak_rss_kb: runs.iter().filter_map(|c| c.peak_rss_kb).max(),
+        threads: runs.iter().filter_map(|c| c.threads).max(),
+        raw,
+        target_metric,
+        per_core,
+        time_series: Vec::new(),
+    }
+}
+
+/// Return the geometric mean of the execution times, or `None` if there is no time or if one of
+/// them is not strictly positive.
+pub fn geomean_execution_time<'a, I>(stats: I) -> Option<f64>
+where
+    I: IntoIterator<Item = &'a Stats>,
+{
+    let mut log_sum = 0.;
+    let mut count = 0;
+    for stats in stats {
+        if stats.execution_time <= 0. || !stats.execution_time.is_finite() {
+            return None;
+        }
+        log_sum += stats.execution_time.ln();
+        count += 1;
+    }
+    if count == 0 {
+        None
+    } else {
+        Some((log_sum / count as f64).exp())
+    }
+}
+
+/// Discard the `percent`% fastest and `percent`% slowest runs, by execution time.
+///
+/// At least one run is always kept.
+pub fn trim(runs: &mut Vec<Counters>, percent: f64) {
+    let mut discarded = (runs.len() as f64 * percent / 100.) as usize;
+    if 2 * discarded >= runs.len() {
+        discarded = runs.len().saturating_sub(1) / 2;
+    }
+    runs.sort_by_key(|counters| counters.wall_clock);
+    runs.truncate(runs.len() - discarded);
+    runs.drain(..discarded);
+}
+
+// ———————————————————————————————— Metrics ————————————————————————————————— //
+
+/// Format a markdown table, all rows must have as many cells as the header.
+pub fn markdown_table(header: &[String], rows: &[Vec<String>]) -> String {
+    let row = |cells: &[String]| format!("| {} |", cells.join(" | "));
+    let mut lines = vec![row(header), format!("|{}", "---|".repeat(header.len()))];
+    lines.extend(rows.iter().map(|cells| row(cells)));
+    lines.join("\n")
+}
+
+/// A named metric, used to render tables.
+pub struct Metric {
+    pub name: &'static str,
+    pub value: Option<f64>,
+    /// Number of decimals to display.
+    pub precision: usize,
+}
+
+impl Metric {
+    fn new(name: &'static str, value: Option<f64>, precision: usize) -> Self {
+        Self {
+            name,
+            value,
+            precision,
+        }
+    }
+
+    /// Format the value, or `n/a` if it is missing.
+    pub fn format(&self) -> String {
+        display(self.value, self.precision, "")
+    }
+}
+
+// —————————————————————————————— Comparisons ——————————————————————————————— //
+
+/// Relative change of each metric between two `Stats`, in percent.
+///
+/// A change is `None` if the metric is missing on either side, or if the baseline is zero.
+#[derive(Serialize)]
+pub struct StatsDelta {
+    cycles: Option<f64>,
+    instructions: Option<f64>,
+    cpu_usage: Option<f64>,
+    instr_per_cycle: Option<f64>,
+    cycles_per_instr: Option<f64>,
+    frontend_stall_rate: Option<f64>,
+    backend_stall_rate: Option<f64>,
+    cache_miss_rate: Option<f64>,
+    memory_bandwidth_gbs: Option<f64>,
+    execution_time: Option<f64>,
+    frequency_scaling: Option<f64>,
+    page_faults: Option<f64>,
+    context_switches: Option<f64>,
+    dtlb_misses: Option<f64>,
+    itlb_misses: Option<f64>,
+    dtlb_misses_per_instr: Option<f64>,
+    peak_rss_kb: Option<f64>,
+    threads: Option<f64>,
+    parallel_efficiency: Option<f64>,
+}
+
+/// A metric that regressed compared to a baseline.
+pub struct Regression {
+    pub name: &'static str,
+    /// Relative change compared to the baseline, in percent.
+    pub change: f64,
+}
+
+impl StatsDelta {
+    /// The changes, in the same order as `Stats::metrics`.
+    pub fn changes(&self) -> Vec<Option<f64>> {
+        vec![
+            self.cycles,
+            self.instructions,
+            self.frequency_scaling,
+            self.instr_per_cycle,
+            self.cycles_per_instr,
+            self.frontend_stall_rate,
+            self.backend_stall_rate,
+            self.cpu_usage,
+            self.cache_miss_rate,
+            self.memory_bandwidth_gbs,
+            self.page_faults,
+            self.context_switches,
+            self.dtlb_misses,
+            self.itlb_misses,
+            self.dtlb_misses_per_instr,
+            self.peak_rss_kb,
+            self.threads,
+            self.parallel_efficiency,
+            self.execution_time,
+        ]
+    }
+
+    /// Return the key metrics (cycles and instructions) that increased by more than `threshold`
+    /// percent.
+    ///
+    /// Return `None` if none of the key metrics could be compared, e.g. because the counters are
+    /// not available on one of the sides.
+    pub fn regressions(&self, threshold: f64) -> Option<Vec<Regression>> {
+        let key_metrics = [("cycles", self.cycles), ("instructions", self.instructions)];
+        if key_metrics.iter().all(|(_, change)| change.is_none()) {
+            return None;
+        }
+        let regressions = key_metrics
+            .iter()
+            .filter_map(|&(name, change)| match change {
+                Some(change) if change > threshold => Some(Regression { name, change }),
+                _ => None,
+            })
+            .collect();
+        Some(regressions)
+    }
+}
+
+/// Return the change from `base` to `new` in percent of `base`.
+fn relative_change(base: Option<f64>, new: Option<f64>) -> Option<f64> {
+    let (base, new) = (base?, new?);
+    if base == 0. {
+        None
+    } else {
+        Some((new - base) / base * 100.)
+    }
+}
+
+/// The side by side comparison of two benchmarks.
+#[derive(Serialize)]
+pub struct Comparison {
+    a: Stats,
+    b: Stats,
+    delta: StatsDelta,
+}
+
+impl Comparison {
+    pub fn new(a: Stats, b: Stats) -> Self {
+        let delta = a.delta(&b);
+        Self { a, b, delta }
+    }
+
+    /// Format the comparison as a markdown table, with one row per metric.
+    pub fn markdown(&self) -> String {
+        let header = ["metric", "A", "B", "change"].iter().map(|s| s.to_string());
+        let rows: Vec<Vec<String>> = self
+            .rows()
+            .into_iter()
+            .map(|(name, a, b, change)| vec![name.to_string(), a, b, change])
+            .collect();
+        markdown_table(&header.collect::<Vec<_>>(), &rows)
+    }
+
+    /// The formatted name, values and change of the metrics measured on at least one side.
+    fn rows(&self) -> Vec<(&'static str, String, String, String)> {
+        self.a
+            .metrics()
+            .iter()
+            .zip(self.b.metrics().iter())
+            .zip(self.delta.changes().iter())
+            .filter(|((a, b), _)| a.value.is_some() || b.value.is_some())
+            .map(|((a, b), change)| {
+                let change = match change {
+                    Some(change) => format!("{:+.1}%", change),
+                    None => String::from("n/a"),
+                };
+                (a.name, a.format(), b.format(), change)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<16}{:>16}{:>16}{:>10}", "", "A", "B", "change")?;
+        for (name, a, b, change) in self.rows() {
+            write!(f, "\n{:<16}{:>16}{:>16}{:>10}", name, a, b, change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::{parse_events, Event};
+
+    #[test]
+    fn counters_subset() {
+        let events = parse_events("cycles,instructions").unwrap();
+        let mut counters = Counters {
+            wall_clock: 1_000_000,
+            ..Counters::default()
+        };
+        for event in events {
+            counters.set(event, 1000);
+        }
+        let stats = Stats::new(counters);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["cycles"], 1000);
+        assert_eq!(json["instr_per_cycle"], 1.0);
+        assert!(json["cpu_usage"].is_null());
+        assert!(json["cache_miss_rate"].is_null());
+        assert!(json["frequency_scaling"].is_null());
+
+        let text = stats.to_string();
+        assert!(text.contains("instr/cycles:\t1.00"));
+        assert!(text.contains("cache miss:\tn/a"));
+    }
+
+    #[test]
+    fn zero_counters() {
+        let mut counters = Counters::default();
+        for &event in &Event::ALL {
+            counters.set(event, 0);
+        }
+        let stats = Stats::new(counters);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(!json.contains("NaN"));
+        assert!(!json.contains("inf"));
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(json["instr_per_cycle"].is_null());
+        assert!(json["cpu_usage"].is_null());
+        assert!(json["cache_miss_rate"].is_null());
+        assert!(json["frequency_scaling"].is_null());
+        assert!(!stats.to_string().contains("NaN"));
+    }
+
+    #[test]
+    fn zero_wall_clock() {
+        let mut counters = all_counters();
+        counters.wall_clock = 0;
+        let stats = Stats::new(counters);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(!json.contains("NaN"));
+        assert!(!json.contains("inf"));
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(json["cpu_usage"].is_null());
+        assert!(json["memory_bandwidth_gbs"].is_null());
+        assert_eq!(json["execution_time"], 0.0);
+        let outputs = [stats.to_string(), stats.markdown(), stats.gnuplot()];
+        for output in &outputs {
+            assert!(!output.contains("NaN") && !output.contains("inf"));
+        }
+        assert!(geomean_execution_time(&[stats]).is_none());
+    }
+
+    fn all_counters() -> Counters {
+        let mut counters = Counters {
+            wall_clock: 2_000_000_000,
+            ..Counters::default()
+        };
+        for &event in &Event::ALL {
+            counters.set(event, 1_000_000);
+        }
+        counters.peak_rss_kb = Some(4096);
+        counters.threads = Some(4);
+        counters
+    }
+
+    #[test]
+    fn cpi_and_bandwidth() {
+        let mut counters = all_counters();
+        counters.instructions = Some(4_000_000);
+        let stats = Stats::new(counters);
+        let cpi = stats.cycles_per_instr.unwrap();
+        assert!((cpi - 1. / stats.instr_per_cycle.unwrap()).abs() < 1e-9);
+
+        // 1M misses of 64 bytes in 2s
+        assert!((stats.memory_bandwidth_gbs.unwrap() - 0.032).abs() < 1e-9);
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert!(json["memory_bandwidth_gbs"].as_f64().unwrap() >= 0.);
+        assert!(stats.to_string().contains("cycles/instr:\t0.25"));
+    }
+
+    #[test]
+    fn stall_rates() {
+        let mut counters = all_counters();
+        counters.stalled_cycles_backend = Some(250_000);
+        counters.stalled_cycles_frontend = None;
+        let stats = Stats::new(counters);
+        assert_eq!(stats.backend_stall_rate, Some(0.25));
+        assert_eq!(stats.frontend_stall_rate, None);
+        assert!(stats.to_string().contains("backend stall:\t25.00%"));
+        assert!(stats.to_string().contains("frontend stall:\tn/a"));
+    }
+
+    #[test]
+    fn parallel_efficiency() {
+        // 3.8 CPUs busy on average on 4 threads
+        let mut counters = all_counters();
+        counters.threads = None;
+        counters.task_clock = Some(7_600_000_000);
+        let stats = Stats::new(counters).with_threads(Some(4));
+        assert!((stats.parallelism.unwrap() - 3.8).abs() < 1e-9);
+        assert!((stats.parallel_efficiency.unwrap() - 0.95).abs() < 1e-9);
+        assert!(stats.to_string().contains("parallel eff.:\t95.00%"));
+        assert!(stats.parallel_efficiency_warning().is_none());
+
+        // The number of threads given takes precedence over the 4 observed threads
+        let mut counters = all_counters();
+        counters.task_clock = Some(2_000_000_000);
+        let stats = Stats::new(counters);
+        assert!((stats.parallel_efficiency.unwrap() - 0.25).abs() < 1e-9);
+        let stats = stats.with_threads(Some(8));
+        assert!((stats.parallel_efficiency.unwrap() - 0.125).abs() < 1e-9);
+        let warning = stats.parallel_efficiency_warning().unwrap();
+        assert!(warning.contains("12.50%"));
+        assert_eq!(
+            Stats::new(all_counters()).with_threads(None),
+            Stats::new(all_counters())
+        );
+    }
+
+    #[test]
+    fn unstable_frequency() {
+        let mut counters = all_counters();
+        counters.cycles = Some(3_000_000);
+        let stats = Stats::new(counters);
+        assert_eq!(stats.frequency_stable, Some(false));
+        assert!(stats.frequency_warning().unwrap().contains("3.00 times"));
+
+        let stats = Stats::new(all_counters());
+        assert_eq!(stats.frequency_stable, Some(true));
+        assert!(stats.frequency_warning().is_none());
+
+        // Without ref cycles the stability is unknown
+        let mut counters = all_counters();
+        counters.ref_cycles = None;
+        let stats = Stats::new(counters);
+        assert_eq!(stats.frequency_stable, None);
+        assert!(stats.frequency_warning().is_none());
+    }
+
+    #[test]
+    fn si_formatting() {
+        assert_eq!(format_count(30_000_000_000), "30.00 G");
+        assert_eq!(format_count(1_234), "1.23 K");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(5_000_000_000_000_000), "5000.00 T");
+
+        // The JSON output keeps the full precision
+        let stats = Stats::new(all_counters());
+        assert!(stats.to_string().contains("cycles:\t\t1.00 M"));
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&stats).unwrap()).unwrap();
+        assert_eq!(json["cycles"], 1_000_000);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut counters = all_counters();
+        counters.cache_references = Some(0);
+        counters.raw = vec![(String::from("raw"), 42)];
+        counters.target_metric = Some(1.5);
+        let stats = Stats::new(counters).with_runs(3);
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(json.contains("\"cache_miss_rate\":null"));
+        assert_eq!(Stats::from_json(&json).unwrap(), stats);
+    }
+
+    #[test]
+    fn trimmed_mean() {
+        let mut runs: Vec<Counters> = (0..19)
+            .map(|i| Counters {
+                wall_clock: 1_000_000 + i * 1000,
+                ..Counters::default()
+            })
+            .collect();
+        runs.push(Counters {
+            wall_clock: 1_000_000_000,
+            ..Counters::default()
+        });
+        trim(&mut runs, 5.);
+
+        assert_eq!(runs.len(), 18);
+        let mean = mean(&runs).wall_clock as f64;
+        assert!((mean - 1_009_500.).abs() < 1000.);
+    }
+
+    #[test]
+    fn trim_keeps_one_run() {
+        let mut runs = vec![
+            Counters::default(),
+            Counters::default(),
+            Counters::default(),
+        ];
+        trim(&mut runs, 30.);
+        assert_eq!(runs.len(), 3);
+        trim(&mut runs, 100.);
+        assert_eq!(runs.len(), 1);
+    }
+
+    #[test]
+    fn geomean() {
+        let a = Stats::new(all_counters());
+        let b = Stats::new(Counters {
+            wall_clock: 8_000_000_000,
+            ..Counters::default()
+        });
+        assert!((geomean_execution_time(&[a, b]).unwrap() - 4.).abs() < 1e-9);
+        assert_eq!(geomean_execution_time(&[]), None);
+        assert_eq!(
+            geomean_execution_time(&[Stats::new(Counters::default())]),
+            None
+        );
+    }
+
+    #[test]
+    fn markdown_output() {
+        let stats = Stats::new(all_counters());
+        let markdown = stats.markdown();
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("| cycles | instructions |"));
+        assert!(lines[1].starts_with("|---|---|"));
+
+        let comparison = Comparison::new(Stats::new(all_counters()), stats).markdown();
+        assert!(comparison.starts_with("| metric | A | B | change |\n|---|---|---|---|"));
+        assert!(comparison.contains("| exec time (s) | 2.00 | 2.00 | +0.0% |"));
+    }
+
+    #[test]
+    fn gnuplot_output() {
+        let runs = vec![all_counters(), all_counters(), Counters::default()];
+        let samples = runs.iter().map(Sample::new).collect();
+        let stats = Stats::new(mean(&runs)).with_samples(samples);
+        let gnuplot = stats.gnuplot();
+        let lines: Vec<&str> = gnuplot.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with('#'));
+        for line in &lines[1..] {
+            assert_eq!(line.split_whitespace().count(), 5);
+        }
+        assert_eq!(lines[3], "2 NaN NaN 0 NaN");
+    }
+
+    #[test]
+    fn percentiles() {
+        // Run times of 1 to 20 ms, in shuffled order
+        let runs: Vec<Counters> = (1..=20)
+            .map(|i| Counters {
+                wall_clock: (i * 7 % 20 + 1) * 1_000_000,
+                cycles: Some(i * 1000),
+                ..Counters::default()
+            })
+            .collect();
+        let samples = runs.iter().map(Sample::new).collect();
+        let stats = Stats::new(mean(&runs)).with_samples(samples);
+        let times = stats.execution_time_percentiles.unwrap();
+        assert!((times.p50 - 0.010).abs() < 1e-9);
+        assert!((times.p95 - 0.019).abs() < 1e-9);
+        assert!((times.p99 - 0.020).abs() < 1e-9);
+        assert_eq!(stats.cycles_percentiles.unwrap().p95, 19_000.);
+        assert!(stats.percentiles().contains("p95 0.0190s"));
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let loaded = Stats::from_json(&json).unwrap();
+        assert_eq!(loaded.execution_time_percentiles, Some(times));
+
+        // Small number of runs
+        let tail = Percentiles::new(&[3., 1., 2.]).unwrap();
+        assert_eq!((tail.p50, tail.p95, tail.p99), (2., 3., 3.));
+        assert_eq!(Percentiles::new(&[]), None);
+
+        // A single run has no percentiles
+        let stats = Stats::new(all_counters()).with_samples(vec![Sample::new(&all_counters())]);
+        assert_eq!(stats.execution_time_percentiles, None);
+        assert!(stats.percentiles().contains("exec time:\tn/a"));
+    }
+
+    #[test]
+    fn delta_with_itself() {
+        let a = Stats::new(all_counters());
+        let b = Stats::new(all_counters());
+        let changes = a.delta(&b).changes();
+        for change in &changes {
+            assert!(change.unwrap().abs() < 1e-9);
+        }
+        assert!(Comparison::new(a, b).to_string().contains("+0.0%"));
+    }
+
+    #[test]
+    fn regression_against_baseline() {
+        let baseline =
+            Stats::from_json(r#"{"cycles":500000,"instructions":1000000,"execution_time":1.0}"#)
+                .unwrap();
+        let stats = Stats::new(all_counters());
+        let regressions = baseline.delta(&stats).regressions(5.).unwrap();
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].name, "cycles");
+        assert!((regressions[0].change - 100.).abs() < 1e-9);
+
+        // Nothing to compare without cycles nor instructions
+        let baseline = Stats::from_json(r#"{"execution_time":1.0}"#).unwrap();
+        assert!(baseline.delta(&stats).regressions(5.).is_none());
+    }
+
+    #[test]
+    fn delta_zero_baseline() {
+        let mut counters = all_counters();
+        counters.cycles = Some(0);
+        let a = Stats::new(counters);
+        let b = Stats::new(all_counters());
+        assert_eq!(a.delta(&b).cycles, None);
     }
 }
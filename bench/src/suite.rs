@@ -0,0 +1,173 @@
+//! Suite
+//!
+//! Benchmark several programs at once and summarize their results.
+use crate::stats::{geomean_execution_time, markdown_table, Metric, Stats};
+use serde::Serialize;
+use std::fmt;
+
+/// The metrics reported for each target, as named by `Stats::metrics`. The execution time must
+/// come last, as the geometric mean is reported below it.
+const COLUMNS: [&str; 4] = ["cycles", "instructions", "instr/cycles", "exec time (s)"];
+
+/// A program of a suite, with its arguments.
+#[derive(Serialize)]
+pub struct Target {
+    pub path: String,
+    pub args: String,
+}
+
+impl Target {
+    /// Parse a suite file, with one target per line as `PATH [ARGS...]`.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn parse_suite(content: &str) -> Result<Vec<Target>, String> {
+        let targets: Vec<Target> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.find(char::is_whitespace) {
+                Some(idx) => Target {
+                    path: line[..idx].to_string(),
+                    args: line[idx..].trim().to_string(),
+                },
+                None => Target {
+                    path: line.to_string(),
+                    args: String::new(),
+                },
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(String::from("the suite is empty"));
+        }
+        Ok(targets)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.args.is_empty() {
+            write!(f, "{}", self.path)
+        } else {
+            write!(f, "{} {}", self.path, self.args)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SuiteEntry {
+    target: Target,
+    stats: Stats,
+}
+
+/// The results of a suite.
+#[derive(Serialize)]
+pub struct Suite {
+    targets: Vec<SuiteEntry>,
+    /// Geometric mean of the execution times, in seconds.
+    geomean_execution_time: Option<f64>,
+}
+
+impl Suite {
+    pub fn new(results: Vec<(Target, Stats)>) -> Self {
+        let geomean_execution_time = geomean_execution_time(results.iter().map(|(_, s)| s));
+        let targets = results
+            .into_iter()
+            .map(|(target, stats)| SuiteEntry { target, stats })
+            .collect();
+        Self {
+            targets,
+            geomean_execution_time,
+        }
+    }
+
+    /// Format the results as a markdown table, with one row per target.
+    pub fn markdown(&self) -> String {
+        let mut header = vec![String::from("target")];
+        header.extend(COLUMNS.iter().map(|name| name.to_string()));
+        markdown_table(&header, &self.rows())
+    }
+
+    /// The cells of the table, with one row per target followed by the geometric mean.
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows: Vec<Vec<String>> = self
+            .targets
+            .iter()
+            .map(|entry| {
+                let metrics = entry.stats.metrics();
+                let mut row = vec![entry.target.to_string()];
+                row.extend(COLUMNS.iter().map(|name| {
+                    metrics
+                        .iter()
+                        .find(|metric| metric.name == *name)
+                        .map(Metric::format)
+                        .unwrap_or_default()
+                }));
+                row
+            })
+            .collect();
+        let mut geomean = vec![String::from("geometric mean")];
+        geomean.resize(COLUMNS.len(), String::new());
+        geomean.push(self.format_geomean());
+        rows.push(geomean);
+        rows
+    }
+
+    fn format_geomean(&self) -> String {
+        match self.geomean_execution_time {
+            Some(geomean) => format!("{:.2}", geomean),
+            None => String::from("n/a"),
+        }
+    }
+}
+
+impl fmt::Display for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<40}", "target")?;
+        for name in &COLUMNS {
+            write!(f, "{:>16}", name)?;
+        }
+        for row in self.rows() {
+            write!(f, "\n{:<40}", row[0])?;
+            for cell in &row[1..] {
+                write!(f, "{:>16}", cell)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::Counters;
+
+    #[test]
+    fn suite_of_identical_targets() {
+        let targets = Target::parse_suite(
+            "# Twice the same program\nbuild/simple.so\n\n  build/simple.so --size 10\n",
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[1].path, "build/simple.so");
+        assert_eq!(targets[1].args, "--size 10");
+
+        let results = targets
+            .into_iter()
+            .map(|target| {
+                let stats = Stats::new(Counters {
+                    wall_clock: 1_500_000_000,
+                    ..Counters::default()
+                });
+                (target, stats)
+            })
+            .collect();
+        let suite = Suite::new(results);
+        assert!((suite.geomean_execution_time.unwrap() - 1.5).abs() < 1e-9);
+        assert!(suite.to_string().contains("build/simple.so --size 10"));
+    }
+
+    #[test]
+    fn empty_suite() {
+        assert!(Target::parse_suite("# Nothing here\n\n").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Sweep
+//!
+//! Benchmark a program for several values of one of its arguments (e.g. its number of threads),
+//! and report the speedup relative to the first value.
+use crate::stats::{markdown_table, Stats};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// A swept parameter, parsed from `NAME=VALUE,VALUE...`.
+///
+/// The parameter is substituted to each `{NAME}` in the arguments of the program.
+#[derive(Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("expected NAME=VALUE,VALUE..., got '{}'", s);
+        let idx = s.find('=').filter(|&idx| idx > 0).ok_or_else(error)?;
+        let values: Vec<String> = s[idx + 1..]
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect();
+        if values.is_empty() {
+            return Err(error());
+        }
+        Ok(Self {
+            name: s[..idx].to_string(),
+            values,
+        })
+    }
+}
+
+impl Parameter {
+    /// The placeholder replaced by the values in the arguments of the program.
+    pub fn placeholder(&self) -> String {
+        format!("{{{}}}", self.name)
+    }
+
+    /// Replace the placeholder by `value` in `args`.
+    pub fn substitute(&self, args: &str, value: &str) -> String {
+        args.replace(&self.placeholder(), value)
+    }
+}
+
+#[derive(Serialize)]
+struct SweepEntry {
+    value: String,
+    stats: Stats,
+    /// Execution time of the first value divided by the execution time of this one.
+    speedup: Option<f64>,
+}
+
+/// The results of a sweep.
+#[derive(Serialize)]
+pub struct Sweep {
+    parameter: String,
+    values: Vec<SweepEntry>,
+}
+
+impl Sweep {
+    pub fn new(parameter: &str, results: Vec<(String, Stats)>) -> Self {
+        let reference = results.first().map(|(_, stats)| stats.execution_time());
+        let values = results
+            .into_iter()
+            .map(|(value, stats)| {
+                let speedup = match (reference, stats.execution_time()) {
+                    (Some(reference), time) if reference > 0. && time > 0. => {
+                        Some(reference / time)
+                    }
+                    _ => None,
+                };
+                SweepEntry {
+                    value,
+                    stats,
+                    speedup,
+                }
+            })
+            .collect();
+        Self {
+            parameter: parameter.to_string(),
+            values,
+        }
+    }
+
+    /// Format the results as a markdown table, with one row per value.
+    pub fn markdown(&self) -> String {
+        let header = vec![
+            self.parameter.clone(),
+            String::from("exec time (s)"),
+            String::from("speedup"),
+        ];
+        let rows: Vec<Vec<String>> = self
+            .values
+            .iter()
+            .map(|entry| {
+                let (time, speedup) = entry.format();
+                vec![entry.value.clone(), time, speedup]
+            })
+            .collect();
+        markdown_table(&header, &rows)
+    }
+}
+
+impl SweepEntry {
+    /// Format the execution time and speedup.
+    fn format(&self) -> (String, String) {
+        let time = format!("{:.2}", self.stats.execution_time());
+        let speedup = match self.speedup {
+            Some(speedup) => format!("{:.2}", speedup),
+            None => String::from("n/a"),
+        };
+        (time, speedup)
+    }
+}
+
+impl fmt::Display for Sweep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<16}{:>16}{:>16}",
+            self.parameter, "exec time (s)", "speedup"
+        )?;
+        for entry in &self.values {
+            let (time, speedup) = entry.format();
+            write!(f, "\n{:<16}{:>16}{:>16}", entry.value, time, speedup)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_parameter() {
+        let parameter: Parameter = "threads=1, 2,4".parse().unwrap();
+        assert_eq!(parameter.name, "threads");
+        assert_eq!(parameter.values, vec!["1", "2", "4"]);
+        assert_eq!(
+            parameter.substitute("-n {threads} --size 10", "2"),
+            "-n 2 --size 10"
+        );
+
+        assert!("=1,2".parse::<Parameter>().is_err());
+        assert!("threads=".parse::<Parameter>().is_err());
+        assert!("threads".parse::<Parameter>().is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! System information
+//!
+//! Describe the host running the benchmarks, as results are only meaningful for a given machine.
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::thread;
+
+const CPUINFO: &str = "/proc/cpuinfo";
+const CPUFREQ: &str = "/sys/devices/system/cpu/cpu0/cpufreq";
+
+/// Information about the host, fields are `None` when they could not be retrieved (e.g. on a
+/// non-Linux system or in a virtual machine without frequency scaling).
+#[derive(Serialize)]
+pub struct SystemInfo {
+    cpu_model: Option<String>,
+    /// Number of logical cores available to bench.
+    cores: Option<usize>,
+    /// The frequency scaling governor of the first core.
+    governor: Option<String>,
+    /// Current frequency of the first core, in MHz.
+    frequency_mhz: Option<f64>,
+}
+
+impl SystemInfo {
+    pub fn collect() -> Self {
+        let cpuinfo = fs::read_to_string(CPUINFO).unwrap_or_default();
+        let scaling_freq = read_sys(&format!("{}/scaling_cur_freq", CPUFREQ))
+            .and_then(|khz| khz.parse::<f64>().ok())
+            .map(|khz| khz / 1000.);
+        Self {
+            cpu_model: cpuinfo_field(&cpuinfo, "model name"),
+            cores: thread::available_parallelism().map(|n| n.get()).ok(),
+            governor: read_sys(&format!("{}/scaling_governor", CPUFREQ)),
+            frequency_mhz: scaling_freq
+                .or_else(|| cpuinfo_field(&cpuinfo, "cpu MHz").and_then(|mhz| mhz.parse().ok())),
+        }
+    }
+}
+
+/// Return the value of the first occurence of `field` in the content of `/proc/cpuinfo`.
+fn cpuinfo_field(cpuinfo: &str, field: &str) -> Option<String> {
+    cpuinfo.lines().find_map(|line| {
+        let idx = line.find(':')?;
+        if line[..idx].trim() == field {
+            Some(line[idx + 1..].trim().to_string()).filter(|value| !value.is_empty())
+        } else {
+            None
+        }
+    })
+}
+
+fn read_sys(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+impl fmt::Display for SystemInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let na = || String::from("n/a");
+        write!(
+            f,
+            "cpu model:\t{}\ncores:\t\t{}\ngovernor:\t{}\nfrequency:\t{}",
+            self.cpu_model.clone().unwrap_or_else(na),
+            self.cores.map(|n| n.to_string()).unwrap_or_else(na),
+            self.governor.clone().unwrap_or_else(na),
+            self.frequency_mhz
+                .map(|mhz| format!("{:.0} MHz", mhz))
+                .unwrap_or_else(na),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cpuinfo() {
+        let cpuinfo = "processor\t: 0\nmodel name\t: Some CPU @ 2.10GHz\ncpu MHz\t\t: 2100.000\n";
+        assert_eq!(
+            cpuinfo_field(cpuinfo, "model name").as_deref(),
+            Some("Some CPU @ 2.10GHz")
+        );
+        assert_eq!(
+            cpuinfo_field(cpuinfo, "cpu MHz").as_deref(),
+            Some("2100.000")
+        );
+        assert_eq!(cpuinfo_field(cpuinfo, "flags"), None);
+    }
+}
@@ -0,0 +1,47 @@
+//! Run the bench binary, to check what it prints on the standard output.
+use std::process::Command;
+
+#[test]
+fn failed_program_json_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["--json", "--counters", "task-clock", "sh", "--args=-c 'exit 3'"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    // The failure is reported on the standard error, the standard output is the JSON document
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("non-zero exit code"));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["status"]["exit_code"], 3);
+}
+
+#[test]
+fn regression_against_baseline() {
+    // A first run gives the baseline, with half the cycles and instructions
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["--json", "--counters", "cycles,instructions,task-clock", "true"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let mut baseline: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    for metric in &["cycles", "instructions"] {
+        if let Some(count) = baseline[metric].as_f64() {
+            baseline[metric] = (count / 2.).into();
+        }
+    }
+    let path = std::env::temp_dir().join("bench_test_baseline.json");
+    std::fs::write(&path, baseline.to_string()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["--json", "--counters", "cycles,instructions,task-clock", "true"])
+        .arg("--baseline")
+        .arg(&path)
+        .output()
+        .unwrap();
+    // Without cycles nor instructions (e.g. in a virtual machine), the comparison fails too
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Regression: cycles") || stderr.contains("could be compared"));
+    serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+}
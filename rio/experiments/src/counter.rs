@@ -3,8 +3,7 @@
 use std::ptr::write_volatile;
 use clap::Clap;
 
-use rio::task;
-use rio::{go, ExecutorId, Runtime};
+use rio::prelude::*;
 
 static mut N: u64= 1000;
 
@@ -63,6 +62,7 @@ fn count(mut rt: Runtime, args: Args) {
 ///
 /// An application performing a compute bound task.
 #[derive(Clap, Clone)]
+#[allow(dead_code)] // Not all the flags are used by every experiment
 struct Args {
     #[clap(default_value = "1000")]
     n_tasks: u64,
@@ -3,8 +3,8 @@
 use clap::Clap;
 use std::ptr::write_volatile;
 
-use rio::task;
-use rio::{go, Data, ExecutorId, Runtime};
+use rio::disjoint_mut;
+use rio::prelude::*;
 
 type DummyData = Data<()>;
 
@@ -67,34 +67,6 @@ impl RandomNumberGenerator {
     }
 }
 
-/// Return three references to objects of a vector. If an index is given more than once, None is
-/// returned instead.
-#[inline]
-fn get_three<'a, T>(
-    vec: &'a mut [T],
-    a: usize,
-    b: usize,
-    c: usize,
-) -> (&'a mut T, Option<&'a mut T>, Option<&'a mut T>) {
-    // Safety: We explicitely check for equality of the indexes to prevent returning two
-    // mutable references to the same object. We also take care of binding the lifetime of the
-    // resulting references to the vector's one.
-    unsafe {
-        let first = &mut *((&mut vec[a]) as *mut T);
-        let second = if b == a {
-            None
-        } else {
-            Some(&mut *((&mut vec[b]) as *mut T))
-        };
-        let third = if c == a || c == b {
-            None
-        } else {
-            Some(&mut *((&mut vec[c]) as *mut T))
-        };
-        (first, second, third)
-    }
-}
-
 // —————————————————————————— Task Based Program ———————————————————————————— //
 
 fn counter() {
@@ -128,13 +100,15 @@ fn counter_3(_a: &(), _b: &(), _c: &mut ()) {
 
 fn count(mut rt: Runtime, args: (Args, Vec<DummyData>, RandomNumberGenerator)) {
     let (args, mut data, mut rng) = args;
+    rt.set_yield_if_not_owner(args.yield_if_not_owner);
     for _ in 0..args.n_tasks {
         let x = rng.rand();
         let idx_1 = x % N_DATA;
         let idx_2 = (x >> DATA_SHIFT) % N_DATA;
         let idx_3 = (x >> (2 * DATA_SHIFT)) % N_DATA;
 
-        let (a, b, c) = get_three(&mut data, idx_1, idx_2, idx_3);
+        let [a, b, c] = disjoint_mut(&mut data, [idx_1, idx_2, idx_3]);
+        let a = a.unwrap();
         match (b, c) {
             (None, None) => {
                 task! {
@@ -171,6 +145,7 @@ fn count(mut rt: Runtime, args: (Args, Vec<DummyData>, RandomNumberGenerator)) {
 ///
 /// An application performing a compute bound task.
 #[derive(Clap, Clone)]
+#[allow(dead_code)] // Not all the flags are used by every experiment
 struct Args {
     #[clap(default_value = "1000")]
     n_tasks: u64,
@@ -181,6 +156,10 @@ struct Args {
     #[clap(short, long, default_value = "2")]
     nb_threads: usize,
 
+    /// Yield the thread after declaring a task owned by another thread
+    #[clap(long = "yield")]
+    yield_if_not_owner: bool,
+
     #[clap(short, long)]
     debug: bool,
 
@@ -5,14 +5,12 @@
 //! - 1D block cyclic
 //! - Round robin
 
-use std::cell::UnsafeCell;
-use std::default::Default;
 use std::ptr::write_volatile;
 
 use clap::Clap;
 
-use rio::task;
-use rio::{go, Data, ExecutorId, Runtime};
+use rio::prelude::*;
+use rio::TileMatrix;
 
 // Number of tiles in a row & column
 // We usa a 32x30 matrix so that we can use a 24 threads 2D block cyclic mapping
@@ -21,7 +19,7 @@ const NB_TILES_COL: usize = 32;
 // Number of counter increments per tasks
 static mut N: u64 = 1000;
 
-type Tiles = [[Data<()>; NB_TILES_ROW]; NB_TILES_COL];
+type Tiles = TileMatrix<()>;
 
 // —————————————————————————————— Entry Point ——————————————————————————————— //
 
@@ -35,7 +33,7 @@ fn main() {
     }
 
     // The tiles
-    let tiles = Default::default();
+    let tiles = Tiles::new(NB_TILES_COL, NB_TILES_ROW);
 
     if !args.block_2d && !args.block_1d {
         // Simple round-robin mapping
@@ -84,7 +82,6 @@ fn lu_fact_block_cyclic(mut rt: Runtime<(usize, usize)>, args: (Tiles, usize)) {
     // `TILE` matrix directly by mutable references, but this is fine as long as two references
     // to the same tile never live together
     let (tiles, n_repeat) = args;
-    let tiles = UnsafeCell::new(tiles);
     let n = if NB_TILES_ROW < NB_TILES_COL {
         NB_TILES_ROW
     } else {
@@ -94,7 +91,7 @@ fn lu_fact_block_cyclic(mut rt: Runtime<(usize, usize)>, args: (Tiles, usize)) {
         for _ in 0..n_repeat {
             for i in 0..n {
                 // Triangular factorization
-                let pivot_tile = get_mut(&tiles, i, i);
+                let pivot_tile = tiles.tile(i, i);
                 task! {
                     rt, trfr,
                     map: (i, i);
@@ -103,7 +100,7 @@ fn lu_fact_block_cyclic(mut rt: Runtime<(usize, usize)>, args: (Tiles, usize)) {
 
                 // Panel update
                 for row in (i + 1)..NB_TILES_COL {
-                    let tile = get_mut(&tiles, row, i);
+                    let tile = tiles.tile(row, i);
                     task! {
                         rt, panel_update,
                         map: (row, i);
@@ -114,7 +111,7 @@ fn lu_fact_block_cyclic(mut rt: Runtime<(usize, usize)>, args: (Tiles, usize)) {
 
                 // Triangular update
                 for col in (i + 1)..NB_TILES_ROW {
-                    let tile = get_mut(&tiles, i, col);
+                    let tile = tiles.tile(i, col);
                     task! {
                         rt, trsm,
                         map: (i, col);
@@ -126,9 +123,9 @@ fn lu_fact_block_cyclic(mut rt: Runtime<(usize, usize)>, args: (Tiles, usize)) {
                 // GEMM update
                 for row in (i + 1)..NB_TILES_COL {
                     for col in (i + 1)..NB_TILES_ROW {
-                        let a = get_mut(&tiles, row, i);
-                        let b = get_mut(&tiles, i, col);
-                        let c = get_mut(&tiles, row, col);
+                        let a = tiles.tile(row, i);
+                        let b = tiles.tile(i, col);
+                        let c = tiles.tile(row, col);
                         task! {
                             rt, gemm,
                             map: (row, col);
@@ -147,7 +144,6 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
     // `TILE` matrix directly by mutable references, but this is fine as long as two references
     // to the same tile never live together
     let (tiles, n_repeat) = args;
-    let tiles = UnsafeCell::new(tiles);
     let n = if NB_TILES_ROW < NB_TILES_COL {
         NB_TILES_ROW
     } else {
@@ -157,7 +153,7 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
         for _ in 0..n_repeat {
             for i in 0..n {
                 // Triangular factorization
-                let pivot_tile = get_mut(&tiles, i, i);
+                let pivot_tile = tiles.tile(i, i);
                 task! {
                     rt, trfr,
                     RW: pivot_tile;
@@ -165,7 +161,7 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
 
                 // Panel update
                 for row in (i + 1)..NB_TILES_COL {
-                    let tile = get_mut(&tiles, row, i);
+                    let tile = tiles.tile(row, i);
                     task! {
                         rt, panel_update,
                         R: pivot_tile;
@@ -175,7 +171,7 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
 
                 // Triangular update
                 for col in (i + 1)..NB_TILES_ROW {
-                    let tile = get_mut(&tiles, i, col);
+                    let tile = tiles.tile(i, col);
                     task! {
                         rt, trsm,
                         R: pivot_tile;
@@ -186,9 +182,9 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
                 // GEMM update
                 for row in (i + 1)..NB_TILES_COL {
                     for col in (i + 1)..NB_TILES_ROW {
-                        let a = get_mut(&tiles, row, i);
-                        let b = get_mut(&tiles, i, col);
-                        let c = get_mut(&tiles, row, col);
+                        let a = tiles.tile(row, i);
+                        let b = tiles.tile(i, col);
+                        let c = tiles.tile(row, col);
                         task! {
                             rt, gemm,
                             R: a, b;
@@ -201,15 +197,6 @@ fn lu_fact_round_robin(mut rt: Runtime, args: (Tiles, usize)) {
     }
 }
 
-// ————————————————————————————————— Utils —————————————————————————————————— //
-
-/// Return a mutable reference to tile (i, j).
-/// This function is unsafe, it is up to the caller to ensure that no two mutable references to the
-/// same tile are alive at the same time. In addition, the lifetime is bond to `TILES`.
-unsafe fn get_mut(tiles: &UnsafeCell<Tiles>, i: usize, j: usize) -> &mut Data<()> {
-    &mut (&mut *tiles.get())[i][j]
-}
-
 // ———————————————————————————————— Tasks ——————————————————————————————————— //
 
 fn counter() {
@@ -249,6 +236,7 @@ fn gemm(_a: &(), _b: &(), _c: &mut ()) {
 
 /// A LU factorization without pivoting.
 #[derive(Clap, Clone)]
+#[allow(dead_code)] // Not all the flags are used by every experiment
 struct Args {
     /// Number of LU factorization
     #[clap(default_value = "1")]
@@ -1,20 +1,18 @@
 //! Matrix Multiplication
 
-use std::cell::UnsafeCell;
-use std::default::Default;
 use std::ptr::write_volatile;
 
 use clap::Clap;
 
-use rio::task;
-use rio::{go, Data, ExecutorId, Runtime};
+use rio::prelude::*;
+use rio::TileMatrix;
 
 // Number of tiles in a row & column
 const NB_TILES: usize = 24;
 // Number of counter increments per tasks
 static mut N: u64 = 1000;
 
-type Tiles = [[Data<()>; NB_TILES]; NB_TILES];
+type Tiles = TileMatrix<()>;
 
 // —————————————————————————————— Executable ———————————————————————————————— //
 
@@ -32,9 +30,9 @@ fn main() {
     let map = get_mapping(&args);
 
     // The matrix tiles
-    let a = Default::default();
-    let b = Default::default();
-    let c = Default::default();
+    let a = Tiles::new(NB_TILES, NB_TILES);
+    let b = Tiles::new(NB_TILES, NB_TILES);
+    let c = Tiles::new(NB_TILES, NB_TILES);
 
     go(nb_threads, map, (nb_repeats, a, b, c), matrix_mult);
 }
@@ -47,30 +45,20 @@ fn get_mapping(args: &Args) -> impl Fn(usize) -> ExecutorId + Clone {
     move |task_id| ExecutorId::new((((task_id - 1) / NB_TILES) % nb_threads) as u32)
 }
 
-/// Return a mutable reference to tile (i, j).
-/// This function is unsafe, it is up to the caller to ensure that no two mutable references to the
-/// same tile are alive at the same time. In addition, the lifetime is bond to `TILES`.
-unsafe fn get_mut(tiles: &UnsafeCell<Tiles>, i: usize, j: usize) -> &mut Data<()> {
-    &mut (&mut *tiles.get())[i][j]
-}
-
 // —————————————————————————— Task Based Program ———————————————————————————— //
 
 fn matrix_mult(mut rt: Runtime, data: (usize, Tiles, Tiles, Tiles)) {
     let (nb_repeats, a, b, c) = data;
 
-    let a = UnsafeCell::new(a);
-    let b = UnsafeCell::new(b);
-    let c = UnsafeCell::new(c);
-
+    // Safety: the tiles of a task belong to distinct matrices, hence never alias.
     unsafe {
         for _ in 0..nb_repeats {
             for j in 0..NB_TILES {
                 for i in 0..NB_TILES {
-                    let c = get_mut(&c, i, j);
+                    let c = c.tile(i, j);
                     for k in 0..NB_TILES {
-                        let a = get_mut(&a, i, k);
-                        let b = get_mut(&b, k, j);
+                        let a = a.tile(i, k);
+                        let b = b.tile(k, j);
                         task! {
                             rt, gemm,
                             R: a, b;
@@ -107,6 +95,7 @@ fn counter() {
 ///
 /// A small MM example using shared (i.e. protected by lock) data objects.
 #[derive(Clap, Clone)]
+#[allow(dead_code)] // Not all the flags are used by every experiment
 struct Args {
     /// Number of runs
     #[clap(default_value = "1")]
@@ -13,21 +13,54 @@
 //!
 //! The `Data` object has the following structure:
 //! - local:  a local copy of the past reads and writes.
+//! - data:   a pointer to the data, which can be a dynamically sized slice.
 //! - shared: a shared state among all workers, itself composed of:
-//!   + data:    a pointer to the data.
-//!   + condvar: a conditional variable used for synchronization.
 //!   + inner:   the record of last reads and writes that have been executed, protected behind a
-//!              lock.
+//!     lock, along with a conditional variable used for synchronization.
 //!
 //! Tasks are represented by a `TaskId`, a unique and monotonically increasing ID. This makes the
 //! local and shared state very space efficient (two `usize`s) and enable fast checking and
 //! maintenance of availability status.
+//!
+//! Dependencies of fine grained tasks are often satisfied within a few microseconds, hence a
+//! thread waiting for a data first spins on a lock-free copy of the shared state before parking on
+//! the conditional variable.
+//!
+//! The accounting itself does not depend on `std` and lives in the `sync_core` module, this module
+//! backs it with `std` primitives.
+//!
+//! A task may panic while accessing a data, the access is then terminated as the thread unwinds so
+//! that a panic never turns into a deadlock, and a panicking write poisons the data: the tasks
+//! accessing it afterward panic instead of observing a partially written value or waiting forever.
 
 use std::cell::UnsafeCell;
 use std::default::Default;
-use std::mem::drop;
+use std::hint;
+use std::mem::{self, drop, MaybeUninit};
 use std::ops::{Deref, DerefMut, Drop};
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError, Weak};
+use std::thread;
+
+use crate::sync_core::{self, Awaited, LocalState, Lock, LockedState};
+
+#[cfg(feature = "metrics")]
+use std::sync::atomic::AtomicU64;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Number of times the readiness of a data is checked before parking the thread.
+const SPIN_ITERATIONS: usize = 100;
+
+/// Number of spin iterations on this machine: spinning is pointless with a single core, as the
+/// thread we are waiting for can not make progress in the meantime.
+fn spin_iterations() -> usize {
+    static ITERATIONS: OnceLock<usize> = OnceLock::new();
+    *ITERATIONS.get_or_init(|| match thread::available_parallelism() {
+        Ok(cores) if cores.get() > 1 => SPIN_ITERATIONS,
+        _ => 0,
+    })
+}
 
 // —————————————————————————————————— Data —————————————————————————————————— //
 
@@ -36,65 +69,254 @@ use std::sync::{Arc, Condvar, Mutex};
 pub struct TaskId(pub(crate) usize);
 
 /// The structure holding the data and responsible for synchronization.
-pub struct Data<T> {
-    local: DataLocalState,
-    shared: Arc<DataSharedState<T>>,
+pub struct Data<T: ?Sized> {
+    local: LocalState,
+    shared: Arc<DataSharedState>,
+    data: Arc<DataCell<T>>,
 }
 
-#[derive(Clone)]
-struct DataLocalState {
-    last_registered_write: usize,
-    nb_reads_since_write: usize,
-    // A data is dirty if it has been written to since last time got access to it
-    dirty: bool,
+/// A handle on a data that does not keep it alive, created with `Data::downgrade`.
+pub struct WeakData<T: ?Sized> {
+    shared: Weak<DataSharedState>,
+    data: Weak<DataCell<T>>,
 }
 
-struct DataSharedState<T> {
-    inner: Mutex<DataLockedState>,
+struct DataSharedState {
+    inner: StdLock,
+    spin: DataSpinState,
+    #[cfg(feature = "metrics")]
+    contention: ContentionCounters,
+}
+
+/// The `std` primitives backing the synchronization core.
+#[derive(Default)]
+struct StdLock {
+    state: Mutex<LockedState>,
     condvar: Condvar,
-    data: UnsafeCell<T>,
 }
 
-struct DataLockedState {
-    last_executed_write: usize,
-    nb_reads_since_write: usize,
-    nb_threads_waiting: usize,
+impl Lock for StdLock {
+    type Guard<'a> = MutexGuard<'a, LockedState>;
+
+    // The mutex is never held while running tasks, and the locked state is consistent between
+    // each of its updates: it can be used even if a thread panicked while holding the mutex.
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait<'a>(&'a self, guard: Self::Guard<'a>) -> Self::Guard<'a> {
+        self.condvar
+            .wait(guard)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify_all(&self) {
+        self.condvar.notify_all();
+    }
+}
+
+/// A lock-free copy of the locked state, only used as a hint when spinning: the locked state is
+/// the source of truth.
+#[derive(Default)]
+struct DataSpinState {
+    last_executed_write: AtomicUsize,
+    nb_reads_since_write: AtomicUsize,
+    /// Whether a write panicked, read by `is_poisoned` without taking the lock.
+    poisoned: AtomicBool,
 }
 
-// Safety: The data is protected by tracking read & write accesses.
-unsafe impl<T: Sync> Sync for DataSharedState<T> {}
+/// The data itself, it has the same layout as `T`.
+#[repr(transparent)]
+struct DataCell<T: ?Sized>(UnsafeCell<T>);
+
+// Safety: The data is protected by tracking read & write accesses. Write tasks may be executed by
+// any thread, hence the `Send` bound.
+unsafe impl<T: ?Sized + Send + Sync> Sync for DataCell<T> {}
+
+// Safety: the copies of a data give concurrent shared accesses to the value (read tasks) and
+// exclusive accesses (write tasks) from any thread holding one, as an `Arc<RwLock<T>>` would, so
+// the value must be both `Send` and `Sync`. The synchronization state only relies on thread-safe
+// primitives.
+unsafe impl<T: ?Sized + Send + Sync> Send for Data<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for Data<T> {}
 
 impl<T> Data<T> {
     pub fn new(data: T) -> Self {
-        let local = DataLocalState {
-            last_registered_write: 0,
-            nb_reads_since_write: 0,
-            dirty: false,
-        };
-        let shared = Arc::new(DataSharedState {
-            inner: Mutex::new(DataLockedState {
-                last_executed_write: 0,
-                nb_reads_since_write: 0,
-                nb_threads_waiting: 0,
+        Self::from_cell(Arc::new(DataCell(UnsafeCell::new(data))))
+    }
+
+    /// Create an independent data holding a copy of the current value, with a fresh
+    /// synchronization state.
+    ///
+    /// This performs synchronization: the copy is a read task, the thread blocks until all the
+    /// writes declared on the data have been executed, and the read is recorded as any other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is shared, i.e. if any other copy of the data is still alive: the
+    /// other copies would not account for the read. During a computation, copy the value from a
+    /// task instead.
+    pub fn clone_inner(&mut self) -> Data<T>
+    where
+        T: Clone,
+    {
+        assert!(
+            Arc::strong_count(&self.shared) == 1,
+            "Can not copy a shared data outside of a task"
+        );
+        // Safety: this container is the only copy of the data, no task can be declared or executed
+        // concurrently with the read.
+        let copy = unsafe { (*self.get_read()).clone() };
+        Data::new(copy)
+    }
+
+    /// Return the value if this container is the only copy left, e.g. once the computation is
+    /// over, or give the container back otherwise. Weak handles do not prevent it.
+    pub fn into_inner(self) -> Result<T, Self> {
+        let Data {
+            local,
+            shared,
+            data,
+        } = self;
+        match Arc::try_unwrap(data) {
+            Ok(cell) => Ok(cell.0.into_inner()),
+            Err(data) => Err(Data {
+                local,
+                shared,
+                data,
             }),
-            condvar: Condvar::new(),
-            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Create a data whose value is not initialized, without paying for the initialization of
+    /// large values. The value is allocated directly on the heap.
+    ///
+    /// # Safety
+    ///
+    /// The first task accessing the data must be a write task that fully initializes the value
+    /// (e.g. with `MaybeUninit::write`), no task may read the value before. Once that write task is
+    /// declared, each executor can convert its copy of the data with `assume_init`.
+    pub unsafe fn new_uninit() -> Data<MaybeUninit<T>> {
+        let data = Arc::into_raw(Arc::<MaybeUninit<T>>::new_uninit());
+        // Safety: `DataCell<MaybeUninit<T>>` has the same layout as `MaybeUninit<T>`, and the
+        // pointer was returned by `Arc::into_raw`.
+        Data::from_cell(Arc::from_raw(data as *const DataCell<MaybeUninit<T>>))
+    }
+}
+
+impl<T> Data<MaybeUninit<T>> {
+    /// Convert a data created with `new_uninit` into an initialized data, keeping the
+    /// synchronization state: the following tasks still wait for the initialization to be
+    /// executed.
+    ///
+    /// # Safety
+    ///
+    /// The write task initializing the value must have been declared (or executed) by the current
+    /// thread. The other copies of the data may be converted independently, but they must all be
+    /// converted before accessing the value as a `T`.
+    pub unsafe fn assume_init(self) -> Data<T> {
+        let Data {
+            local,
+            shared,
+            data,
+        } = self;
+        // Safety: `DataCell<T>` has the same layout as `DataCell<MaybeUninit<T>>`.
+        let data = Arc::from_raw(Arc::into_raw(data) as *const DataCell<T>);
+        Data {
+            local,
+            shared,
+            data,
+        }
+    }
+}
+
+impl<T> Data<Vec<T>> {
+    /// Create a data holding an empty vector with room for at least `capacity` elements, so that
+    /// the tasks filling it up to `capacity` do not reallocate it.
+    pub fn new_vec(capacity: usize) -> Self {
+        Data::new(Vec::with_capacity(capacity))
+    }
+}
+
+impl<T> Data<[T]> {
+    /// Create a data holding a slice, whose size is only known at runtime.
+    pub fn from_boxed_slice(data: Box<[T]>) -> Self {
+        let data: Arc<[T]> = Arc::from(data);
+        // Safety: `DataCell<[T]>` has the same layout as `[T]`, and the pointer was returned by
+        // `Arc::into_raw`.
+        let data = unsafe { Arc::from_raw(Arc::into_raw(data) as *const DataCell<[T]>) };
+        Self::from_cell(data)
+    }
+}
+
+impl<T: ?Sized> Data<T> {
+    fn from_cell(data: Arc<DataCell<T>>) -> Self {
+        let local = LocalState::new();
+        let shared = Arc::new(DataSharedState {
+            inner: StdLock::default(),
+            spin: DataSpinState::default(),
+            #[cfg(feature = "metrics")]
+            contention: ContentionCounters::default(),
         });
-        Self { local, shared }
+        Self {
+            local,
+            shared,
+            data,
+        }
     }
 
-    fn write_is_ready(&self, inner: &DataLockedState) -> bool {
-        let state = &self.local;
+    /// Reset the synchronization state of the data, so that it can be used in a new and
+    /// independent computation.
+    ///
+    /// # Safety
+    ///
+    /// Task IDs restart from the beginning after a reset, the data must only be used by fresh
+    /// runtimes afterward (e.g. in a new call to `go`). All the executors must reset in lockstep:
+    /// every copy of the data used in the new computation must be cloned from the reset container.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is shared, i.e. if any other copy of the data is still alive.
+    pub unsafe fn reset(&mut self) {
+        let shared = Arc::get_mut(&mut self.shared).expect("Can not reset a shared data");
+        *shared
+            .inner
+            .state
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = LockedState::new();
+        shared.spin = DataSpinState::default();
+        #[cfg(feature = "metrics")]
+        {
+            shared.contention = ContentionCounters::default();
+        }
+        self.local = LocalState::new();
+    }
 
-        // A write is ready if all the previous reads and writes have been executed.
-        let reads_are_done = inner.nb_reads_since_write == state.nb_reads_since_write;
-        let writes_are_done = inner.last_executed_write == state.last_registered_write;
+    /// Whether the awaited write is ready, according to the lock-free copy of the shared state.
+    fn write_may_be_ready(&self) -> bool {
+        let spin = &self.shared.spin;
+        let reads_are_done =
+            spin.nb_reads_since_write.load(Ordering::Relaxed) == self.local.nb_reads_since_write;
+        let writes_are_done =
+            spin.last_executed_write.load(Ordering::Relaxed) == self.local.last_registered_write;
         reads_are_done && writes_are_done
     }
 
-    fn read_is_ready(&self, inner: &DataLockedState) -> bool {
-        // A read is ready if all the previous writes have been executed.
-        inner.last_executed_write == self.local.last_registered_write
+    /// Whether the awaited read is ready, according to the lock-free copy of the shared state.
+    fn read_may_be_ready(&self) -> bool {
+        let last_executed_write = self.shared.spin.last_executed_write.load(Ordering::Relaxed);
+        last_executed_write == self.local.last_registered_write
+    }
+
+    /// Spin until `is_ready` returns true, or for at most `spin_iterations()`.
+    fn spin_until(&self, is_ready: fn(&Self) -> bool) {
+        for _ in 0..spin_iterations() {
+            if is_ready(self) {
+                return;
+            }
+            hint::spin_loop();
+        }
     }
 
     /// Declare a read task on the data, without executing it.
@@ -106,7 +328,7 @@ impl<T> Data<T> {
     ///
     /// Each thread should call this function exactly once per read task on the data container.
     pub unsafe fn declare_read(&mut self) {
-        self.local.nb_reads_since_write += 1;
+        self.local.declare_read();
     }
 
     /// Declare a write task on the data, without executing it.
@@ -118,9 +340,7 @@ impl<T> Data<T> {
     ///
     /// Each thread should call this function exactly once per write task on the data container.
     pub unsafe fn declare_write(&mut self, task_id: TaskId) {
-        self.local.last_registered_write = task_id.0;
-        self.local.nb_reads_since_write = 0;
-        self.local.dirty = true;
+        self.local.declare_write(task_id.0);
     }
 
     /// Get a reference to the data, in read-only mode.
@@ -138,28 +358,29 @@ impl<T> Data<T> {
     pub unsafe fn get_read(&mut self) -> Ref<'_, T> {
         // If the data has not been invalidated since last time we got access to it no need to
         // synchronize.
-        if !self.local.dirty {
-            return Ref(self);
+        if !self.local.is_dirty() {
+            return Ref(self, None);
         }
 
-        let mut inner = self.shared.inner.lock().unwrap();
+        self.spin_until(Self::read_may_be_ready);
+
+        let awaited = self.local.awaited_read();
+        let inner = self.check_poisoned(self.shared.inner.lock());
 
         // Data is ready
-        if self.read_is_ready(&inner) {
+        if awaited.is_satisfied(&inner) {
             drop(inner);
-            return Ref(self);
+            return Ref(self, None);
         }
 
         // Sleep until data is ready
-        inner.nb_threads_waiting += 1;
-        loop {
-            inner = self.shared.condvar.wait(inner).unwrap();
-            if self.read_is_ready(&inner) {
-                inner.nb_threads_waiting -= 1;
-                drop(inner);
-                return Ref(self);
-            }
-        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let inner = sync_core::wait_for(&self.shared.inner, inner, awaited);
+        drop(self.check_poisoned(inner));
+        #[cfg(feature = "metrics")]
+        self.shared.contention.record(start);
+        Ref(self, None)
     }
 
     /// Get a reference to the data, in read-write mode.
@@ -175,26 +396,109 @@ impl<T> Data<T> {
     /// data state adequately. To ensure that all the above condition holds, this function should
     /// never be called directly but rather used through the `task!` macro.
     pub unsafe fn get_write(&mut self, task_id: TaskId) -> RefMut<'_, T> {
-        let mut inner = self.shared.inner.lock().unwrap();
+        self.spin_until(Self::write_may_be_ready);
+        let awaited = self.local.awaited_write();
+        let mut inner = self.check_poisoned(self.shared.inner.lock());
 
         // Data is ready
-        if self.write_is_ready(&inner) {
+        if awaited.is_satisfied(&inner) {
+            inner.start_write();
             drop(inner);
             return RefMut(self, task_id);
         }
 
         // Sleep until data is ready
-        inner.nb_threads_waiting += 1;
-        loop {
-            inner = self.shared.condvar.wait(inner).unwrap();
-            if self.write_is_ready(&inner) {
-                inner.nb_threads_waiting -= 1;
-                drop(inner);
-                return RefMut(self, task_id);
-            }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let inner = sync_core::wait_for(&self.shared.inner, inner, awaited);
+        let mut inner = self.check_poisoned(inner);
+        inner.start_write();
+        drop(inner);
+        #[cfg(feature = "metrics")]
+        self.shared.contention.record(start);
+        RefMut(self, task_id)
+    }
+
+    /// Block until the write task `task_id` (or a later one) has been executed.
+    ///
+    /// This is an explicit synchronization point which does not declare any task, it can be used
+    /// for instance to snapshot the data from a thread that is not part of the computation.
+    pub fn wait_until(&self, task_id: TaskId) {
+        let inner = self.shared.inner.lock();
+        let awaited = Awaited::AtLeast { write: task_id.0 };
+        if !awaited.is_satisfied(&inner) {
+            drop(sync_core::wait_for(&self.shared.inner, inner, awaited));
+        }
+    }
+
+    /// Block until the data is at most `max_in_flight` tasks behind `task_id`, or until all the
+    /// writes declared so far have been executed.
+    ///
+    /// This is used for flow control, to prevent threads from declaring tasks far ahead of the
+    /// execution.
+    pub fn wait_in_flight(&self, task_id: TaskId, max_in_flight: usize) {
+        let oldest_allowed = task_id.0.saturating_sub(max_in_flight);
+        let target = self.local.last_registered_write.min(oldest_allowed);
+        self.wait_until(TaskId(target));
+    }
+
+    /// Return the ID of the last write task executed on the data, or 0 if there is none.
+    ///
+    /// Unlike `wait_until`, this function never blocks.
+    pub fn version(&self) -> u64 {
+        self.shared.inner.lock().last_executed_write() as u64
+    }
+
+    /// Return a copy of the value, or `None` if a write task is being executed on the data.
+    ///
+    /// This is a best-effort snapshot taken outside of the synchronization protocol, e.g. to
+    /// monitor a computation from another thread: it does not wait for the declared tasks and
+    /// the value may be outdated as soon as it is returned.
+    pub fn try_read_latest(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        let inner = self.shared.inner.lock();
+        if inner.is_writing() {
+            return None;
+        }
+        // Safety: writes start under the lock of the shared state, by raising the `writing` flag,
+        // no write can start while the lock is held.
+        Some(unsafe { *self.data.0.get() })
+    }
+
+    /// Return a mutable reference to the data if this container is the only copy left, e.g. once
+    /// the computation is over.
+    ///
+    /// No synchronization is needed in that case, as no other thread can access the data.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        Arc::get_mut(&mut self.data).map(|cell| cell.0.get_mut())
+    }
+
+    /// Return a handle that does not keep the data alive, e.g. for an observer that must not
+    /// outlive the computation.
+    ///
+    /// Weak handles do not prevent `into_inner`, but `get_mut` and `reset` fail while they exist.
+    pub fn downgrade(&self) -> WeakData<T> {
+        WeakData {
+            shared: Arc::downgrade(&self.shared),
+            data: Arc::downgrade(&self.data),
         }
     }
 
+    /// Return true if a write task panicked on the data, whose value may then be inconsistent.
+    ///
+    /// The tasks accessing a poisoned data panic instead of waiting for it. The poisoning is
+    /// cleared by `reset`.
+    pub fn is_poisoned(&self) -> bool {
+        self.shared.spin.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Return true if the two containers hold the same data, i.e. if one is a clone of the other.
+    pub fn ptr_eq(this: &Data<T>, other: &Data<T>) -> bool {
+        Arc::ptr_eq(&this.shared, &other.shared)
+    }
+
     /// Mark a read operation as terminated.
     /// The read declaration is performed by this function.
     ///
@@ -203,15 +507,15 @@ impl<T> Data<T> {
     /// This function must be called exactly once for each read operation executed by the thread on
     /// this data, failure to do so may result in synchronization error and data races.
     unsafe fn terminate_read(&mut self) {
-        self.declare_read();
-        self.local.dirty = false;
-        let mut inner = self.shared.inner.lock().unwrap();
+        self.local.terminate_read();
+        let mut inner = self.shared.inner.lock();
 
         // Update shared state & wake up waiting threads
-        inner.nb_reads_since_write += 1;
-        if inner.nb_threads_waiting > 0 {
-            self.shared.condvar.notify_all();
-        }
+        inner.execute_read();
+        let spin = &self.shared.spin;
+        spin.nb_reads_since_write
+            .store(inner.nb_reads_since_write, Ordering::Relaxed);
+        sync_core::wake_up(&self.shared.inner, &inner);
     }
 
     /// Mark a write operation as terminated.
@@ -222,24 +526,134 @@ impl<T> Data<T> {
     /// This function must be called exactly once for each write operation executed by the thread
     /// on this data, failure to do so may result in synchronization error and data races.
     unsafe fn terminate_write(&mut self, task_id: TaskId) {
-        self.declare_write(task_id);
-        self.local.dirty = false;
-        let mut inner = self.shared.inner.lock().unwrap();
+        self.local.terminate_write(task_id.0);
+        let panicking = thread::panicking();
+        if panicking {
+            self.shared.spin.poisoned.store(true, Ordering::Relaxed);
+        }
+        // The lock is never held while running tasks, blocking on it is fine even while unwinding.
+        // The poisoning is recorded under the lock, so that the parked threads observe it.
+        let mut inner = self.shared.inner.lock();
 
         // Update shared state & wake up waiting threads
-        inner.last_executed_write = task_id.0;
-        inner.nb_reads_since_write = 0;
-        if inner.nb_threads_waiting > 0 {
-            self.shared.condvar.notify_all();
+        inner.execute_write(task_id.0);
+        if panicking {
+            inner.poison();
+        }
+        let spin = &self.shared.spin;
+        spin.last_executed_write.store(task_id.0, Ordering::Relaxed);
+        spin.nb_reads_since_write.store(0, Ordering::Relaxed);
+        sync_core::wake_up(&self.shared.inner, &inner);
+    }
+
+    /// Return `inner`, the guard of the locked state, or release it and panic if the data is
+    /// poisoned.
+    fn check_poisoned<'a>(
+        &self,
+        inner: MutexGuard<'a, LockedState>,
+    ) -> MutexGuard<'a, LockedState> {
+        if inner.is_poisoned() || self.is_poisoned() {
+            drop(inner);
+            panic!("A task panicked while writing to the data");
+        }
+        inner
+    }
+
+    /// Hold the lock of the shared state, e.g. to terminate a task while the lock is contended.
+    #[cfg(test)]
+    pub(crate) fn lock_shared_state(&self) -> MutexGuard<'_, LockedState> {
+        self.shared.inner.lock()
+    }
+}
+
+impl<T: ?Sized> WeakData<T> {
+    /// Return a new container for the data if it is still alive.
+    ///
+    /// The container starts from the tasks executed so far rather than from the ones declared by
+    /// an executor: it can observe the data (e.g. with `version` or `try_read_latest`), but must
+    /// not take part in a computation.
+    pub fn upgrade(&self) -> Option<Data<T>> {
+        let data = self.data.upgrade()?;
+        let shared = self.shared.upgrade()?;
+        let inner = shared.inner.lock();
+        let local = LocalState {
+            last_registered_write: inner.last_executed_write,
+            nb_reads_since_write: inner.nb_reads_since_write,
+            dirty: false,
+        };
+        drop(inner);
+        Some(Data {
+            local,
+            shared,
+            data,
+        })
+    }
+}
+
+impl<T: ?Sized> Clone for WeakData<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<T: ?Sized> Data<T> {
+    /// Return statistics about the threads that blocked while accessing the data.
+    pub fn contention_stats(&self) -> ContentionStats {
+        ContentionStats {
+            times_blocked: self.shared.contention.times_blocked.load(Ordering::Relaxed),
+            total_wait_ns: self.shared.contention.total_wait_ns.load(Ordering::Relaxed),
         }
     }
 }
 
-impl<T> Clone for Data<T> {
+#[cfg(any(test, feature = "introspection"))]
+impl<T: ?Sized> Data<T> {
+    /// Return the number of reads executed since the last executed write, on all the executors.
+    pub fn committed_reads(&self) -> usize {
+        self.shared.inner.lock().nb_reads_since_write
+    }
+
+    /// Return true if a write has been declared on this container since it last accessed the data,
+    /// in which case the next read has to synchronize.
+    pub fn is_locally_dirty(&self) -> bool {
+        self.local.is_dirty()
+    }
+}
+
+/// Collect the values of the tiles once the computation is over, i.e. after `go` returned.
+///
+/// # Panics
+///
+/// Panics if one of the tiles is still shared with another container, in which case its value
+/// could still be modified.
+pub fn collect_tiles<T: Clone>(tiles: &[Data<T>]) -> Vec<T> {
+    tiles
+        .iter()
+        .enumerate()
+        .map(|(idx, tile)| {
+            let nb_copies = Arc::strong_count(&tile.data);
+            assert!(
+                nb_copies == 1,
+                "collect_tiles: tile {} is still shared by {} other containers, tiles can only be collected once the computation is over",
+                idx,
+                nb_copies - 1
+            );
+            // Safety: this container is the only copy left, no other thread can access the data.
+            unsafe { (*tile.data.0.get()).clone() }
+        })
+        .collect()
+}
+
+impl<T: ?Sized> Clone for Data<T> {
     fn clone(&self) -> Self {
         Self {
             local: self.local.clone(),
             shared: self.shared.clone(),
+            data: self.data.clone(),
         }
     }
 }
@@ -253,44 +667,113 @@ where
     }
 }
 
+// ———————————————————————————————— Metrics ————————————————————————————————— //
+
+/// Statistics about the threads blocked on a data, available with the `metrics` feature.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ContentionStats {
+    /// Number of times a thread had to wait for the data to become ready.
+    pub times_blocked: u64,
+    /// Total time spent waiting by all the threads, in nanoseconds.
+    pub total_wait_ns: u64,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct ContentionCounters {
+    times_blocked: AtomicU64,
+    total_wait_ns: AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl ContentionCounters {
+    /// Record a wait that started at `start` and just ended.
+    fn record(&self, start: Instant) {
+        let wait_ns = start.elapsed().as_nanos() as u64;
+        self.times_blocked.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_ns.fetch_add(wait_ns, Ordering::Relaxed);
+    }
+}
+
 // ————————————————————————————— Smart Pointers ————————————————————————————— //
 
 /// A read-only smart pointer holding the data.
-pub struct Ref<'data, T>(&'data mut Data<T>);
+///
+/// If the pointer has been obtained by downgrading a `RefMut`, the write is only marked as
+/// terminated once the pointer is dropped.
+pub struct Ref<'data, T: ?Sized>(&'data mut Data<T>, Option<TaskId>);
 
 /// A read-write smart pointer holding the data.
-pub struct RefMut<'data, T>(&'data mut Data<T>, TaskId);
+pub struct RefMut<'data, T: ?Sized>(&'data mut Data<T>, TaskId);
+
+impl<'data, T: ?Sized> Ref<'data, T> {
+    /// Try to turn the pointer back into a read-write pointer for the write task `task_id`.
+    ///
+    /// Upgrading is possible only if the pointer has been obtained by downgrading a `RefMut` of
+    /// the same task: in that case the write is still in progress and no other task can access the
+    /// data. A read can not be upgraded as other reads of the data may execute concurrently, in
+    /// which case the pointer is returned unchanged.
+    pub fn try_upgrade(self, task_id: TaskId) -> Result<RefMut<'data, T>, Self> {
+        if self.1 != Some(task_id) {
+            return Err(self);
+        }
+        // Safety: `self` is forgotten, the data is borrowed by a single pointer at any time.
+        let data = unsafe { std::ptr::read(&self.0) };
+        mem::forget(self);
+        Ok(RefMut(data, task_id))
+    }
+}
 
-impl<'data, T> Deref for Ref<'data, T> {
+impl<'data, T: ?Sized> RefMut<'data, T> {
+    /// Turn the pointer into a read-only pointer.
+    ///
+    /// The write is only marked as terminated once the returned pointer is dropped, hence the
+    /// tasks depending on this write are still blocked in the meantime.
+    pub fn downgrade(self) -> Ref<'data, T> {
+        let task_id = self.1;
+        // Safety: `self` is forgotten, the data is borrowed by a single pointer at any time.
+        let data = unsafe { std::ptr::read(&self.0) };
+        mem::forget(self);
+        Ref(data, Some(task_id))
+    }
+}
+
+impl<'data, T: ?Sized> Deref for Ref<'data, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.0.shared.data.get() }
+        unsafe { &*self.0.data.0.get() }
     }
 }
 
-impl<'data, T> Deref for RefMut<'data, T> {
+impl<'data, T: ?Sized> Deref for RefMut<'data, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.0.shared.data.get() }
+        unsafe { &*self.0.data.0.get() }
     }
 }
 
-impl<'data, T> DerefMut for RefMut<'data, T> {
+impl<'data, T: ?Sized> DerefMut for RefMut<'data, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { &mut *self.0.shared.data.get() }
+        unsafe { &mut *self.0.data.0.get() }
     }
 }
 
-impl<'data, T> Drop for Ref<'data, T> {
+impl<'data, T: ?Sized> Drop for Ref<'data, T> {
     fn drop(&mut self) {
         // Safety: the destructor will run only once.
-        unsafe { self.0.terminate_read() }
+        unsafe {
+            match self.1 {
+                Some(task_id) => self.0.terminate_write(task_id),
+                None => self.0.terminate_read(),
+            }
+        }
     }
 }
 
-impl<'data, T> Drop for RefMut<'data, T> {
+impl<'data, T: ?Sized> Drop for RefMut<'data, T> {
     fn drop(&mut self) {
         // Safety: the destructor will run only once.
         unsafe { self.0.terminate_write(self.1) }
@@ -0,0 +1,33 @@
+//! # Disjoint Borrows
+//!
+//! Task programs often select a handful of data containers by computed indexes, and need mutable
+//! references to all of them at once to declare a task.
+
+/// Return mutable references to the elements of `slice` at the given indexes.
+///
+/// Indexes can be repeated, only the first occurrence of an index gets a reference and the next
+/// ones get `None`, so that no element is ever borrowed twice.
+///
+/// # Panics
+///
+/// Panics if any of the indexes is out of bounds.
+pub fn disjoint_mut<T, const K: usize>(slice: &mut [T], idx: [usize; K]) -> [Option<&mut T>; K] {
+    let len = slice.len();
+    let ptr = slice.as_mut_ptr();
+    std::array::from_fn(|k| {
+        let i = idx[k];
+        assert!(
+            i < len,
+            "index out of bounds: the len is {} but the index is {}",
+            len,
+            i
+        );
+        if idx[..k].contains(&i) {
+            None
+        } else {
+            // Safety: the index is in bounds, and this is its first occurrence hence the element
+            // is borrowed only once. The lifetime is bound to the one of the slice.
+            Some(unsafe { &mut *ptr.add(i) })
+        }
+    })
+}
@@ -1,14 +1,29 @@
+extern crate alloc;
+
 mod data;
+mod disjoint;
+mod once_data;
+pub mod prelude;
 mod runtime;
+pub mod sync_core;
+mod task_graph;
 mod task_macro;
+mod tile_matrix;
+mod write_chain;
 
 pub use data::*;
+pub use disjoint::*;
+pub use once_data::*;
 pub use runtime::*;
-pub use task_macro::*;
+pub use task_graph::*;
+pub use tile_matrix::*;
+pub use write_chain::*;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::mem::MaybeUninit;
+    use std::sync::{Arc, Mutex};
 
     fn add(a: &i32, b: &mut i32) {
         *b += *a;
@@ -40,6 +55,377 @@ mod tests {
         }
     }
 
+    fn check_is_double_answer(a: &i32) {
+        assert_eq!(*a, 84);
+    }
+
+    fn second_control_flow(mut rt: Runtime, mut b: Data<i32>) {
+        task! {
+            rt, double,
+            RW: b;
+        }
+        task! {
+            rt, check_is_double_answer,
+            R: b;
+        }
+    }
+
+    fn increment(a: &mut usize) {
+        *a += 1;
+    }
+
+    fn slow_increment(a: &mut usize) {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        *a += 1;
+    }
+
+    fn check_is_thousand(a: &usize) {
+        assert_eq!(*a, 1000);
+    }
+
+    fn check_is_ten(a: &usize) {
+        assert_eq!(*a, 10);
+    }
+
+    fn fast_chain(mut rt: Runtime, mut a: Data<usize>) {
+        for _ in 0..1000 {
+            task! {
+                rt, increment,
+                RW: a;
+            }
+        }
+        task! {
+            rt, check_is_thousand,
+            R: a;
+        }
+    }
+
+    fn slow_chain(mut rt: Runtime, mut a: Data<usize>) {
+        for _ in 0..10 {
+            task! {
+                rt, slow_increment,
+                RW: a;
+            }
+        }
+        task! {
+            rt, check_is_ten,
+            R: a;
+        }
+    }
+
+    fn yielding_chain(mut rt: Runtime, a: Data<usize>) {
+        rt.set_yield_if_not_owner(true);
+        fast_chain(rt, a);
+    }
+
+    fn accumulate(a: &usize, sum: &mut usize) {
+        *sum += *a;
+    }
+
+    fn check_sum(sum: &usize) {
+        assert_eq!(*sum, 5050);
+    }
+
+    fn fan_out_fan_in(mut rt: Runtime, args: (Data<usize>, Vec<Data<usize>>)) {
+        let (mut a, mut sums) = args;
+        for _ in 0..100 {
+            task! {
+                rt, increment,
+                RW: a;
+            }
+            // All the reads must complete before the next increment
+            for sum in sums.iter_mut() {
+                task! {
+                    rt, accumulate,
+                    R: a;
+                    RW: sum;
+                }
+            }
+        }
+        for sum in sums.iter_mut() {
+            task! {
+                rt, check_sum,
+                R: sum;
+            }
+        }
+    }
+
+    fn fill(tile: &mut [i32]) {
+        for (idx, x) in tile.iter_mut().enumerate() {
+            *x = idx as i32;
+        }
+    }
+
+    fn check_filled(tile: &[i32]) {
+        assert_eq!(tile.len(), 1000);
+        assert!(tile.iter().enumerate().all(|(idx, x)| *x == idx as i32));
+    }
+
+    fn blur(src: &[i32], dst: &mut [i32]) {
+        for (i, cell) in dst.iter_mut().enumerate() {
+            let start = i.saturating_sub(1);
+            let end = (i + 2).min(src.len());
+            *cell = src[start..end].iter().sum();
+        }
+    }
+
+    fn check_blurred(tile: &[i32]) {
+        assert_eq!(tile, &[0, 1, 2, 3, 2, 1, 0]);
+    }
+
+    fn stencil(mut rt: Runtime, args: (Data<[i32]>, Data<[i32]>)) {
+        let (mut src, mut dst) = args;
+        for _ in 0..2 {
+            task! {
+                rt, blur,
+                RW_from: src -> dst;
+            }
+            std::mem::swap(&mut src, &mut dst);
+        }
+        task! {
+            rt, check_blurred,
+            R: src;
+        }
+    }
+
+    fn init_answer(a: &mut MaybeUninit<u64>) {
+        a.write(42);
+    }
+
+    fn check_is_answer_u64(a: &u64) {
+        assert_eq!(*a, 42);
+    }
+
+    fn uninit_flow(mut rt: Runtime, mut a: Data<MaybeUninit<u64>>) {
+        task! {
+            rt, init_answer,
+            RW: a;
+        }
+        let mut a = unsafe { a.assume_init() };
+        task! {
+            rt, check_is_answer_u64,
+            R: a;
+        }
+    }
+
+    fn slice_control_flow(mut rt: Runtime, mut tile: Data<[i32]>) {
+        task! {
+            rt, fill,
+            RW: tile;
+        }
+        task! {
+            rt, check_filled,
+            R: tile;
+        }
+    }
+
+    fn check_is_three(a: &i32) {
+        assert_eq!(*a, 3);
+    }
+
+    fn prefix_sums(mut rt: Runtime, matrix: TileMatrix<i32>) {
+        // Safety: the two tiles of each task are distinct
+        for i in 0..matrix.rows() {
+            for j in 1..matrix.cols() {
+                let left = unsafe { matrix.tile(i, j - 1) };
+                let tile = unsafe { matrix.tile(i, j) };
+                task! {
+                    rt, add,
+                    R: left;
+                    RW: tile;
+                }
+            }
+            let last = unsafe { matrix.tile(i, matrix.cols() - 1) };
+            task! {
+                rt, check_is_three,
+                R: last;
+            }
+        }
+    }
+
+    type CancelArgs = (CancellationToken, Data<usize>, Arc<Mutex<Vec<usize>>>);
+
+    fn cancelled_flow(mut rt: Runtime, args: CancelArgs) {
+        let (token, mut a, stops) = args;
+        for i in 0..100_000 {
+            if i == 100 {
+                token.cancel();
+            }
+            if rt.is_cancelled() {
+                stops.lock().unwrap().push(i);
+                return;
+            }
+            task! {
+                rt, increment,
+                RW: a;
+            }
+        }
+    }
+
+    type ConvergenceArgs = (Data<f64>, Arc<Mutex<Vec<usize>>>);
+
+    fn converging_flow(mut rt: Runtime, last_residual: &Mutex<f64>, args: ConvergenceArgs) {
+        let (mut residual, stops) = args;
+        let iterate = |residual: &mut f64| {
+            *residual /= 2.0;
+            *last_residual.lock().unwrap() = *residual;
+        };
+        for i in 0..100_000 {
+            if rt.is_cancelled() {
+                stops.lock().unwrap().push(i);
+                return;
+            }
+            task! {
+                rt, iterate,
+                RW: residual;
+            }
+        }
+    }
+
+    type BoundedArgs = (Data<usize>, Arc<Mutex<usize>>);
+
+    fn bounded_flow(mut rt: Runtime, args: BoundedArgs) {
+        let (mut a, max_lead) = args;
+        rt.set_max_in_flight(Some(4));
+        for i in 1..=30 {
+            task! {
+                rt, slow_increment,
+                RW: a;
+            }
+            let lead = i - a.version() as usize;
+            let mut max_lead = max_lead.lock().unwrap();
+            *max_lead = (*max_lead).max(lead);
+        }
+    }
+
+    fn count_owned(mut rt: Runtime, owned: &mut usize, counts: Arc<Mutex<Vec<usize>>>) {
+        for _ in 0..10 {
+            let mut count = || *owned += 1;
+            task! {
+                rt, count
+            }
+        }
+        counts.lock().unwrap().push(*owned);
+    }
+
+    type Owners = Arc<Mutex<Vec<(u32, usize, usize)>>>;
+
+    fn diagonal_mapping((i, j): (usize, usize)) -> ExecutorId {
+        ExecutorId::new(((i + j) % 2) as u32)
+    }
+
+    fn record_owners(mut rt: Runtime<(usize, usize)>, thread_id: &mut u32, owners: Owners) {
+        for i in 0..2 {
+            for j in 0..2 {
+                let record = || owners.lock().unwrap().push((*thread_id, i, j));
+                task! {
+                    rt, record,
+                    map: (i, j)
+                }
+            }
+        }
+    }
+
+    type Draws = Arc<Mutex<Vec<(u32, Vec<u64>)>>>;
+
+    fn draw_random(rt: Runtime, draws: Draws) {
+        // Xorshift64
+        let mut state = rt.seeded_per_executor(42);
+        let values = (0..4)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect();
+        let thread_id = rt.executor_id().thread_id;
+        draws.lock().unwrap().push((thread_id, values));
+    }
+
+    fn record_thread(threads: &mut Vec<std::thread::ThreadId>) {
+        threads.push(std::thread::current().id());
+    }
+
+    fn thread_chain(mut rt: Runtime, mut threads: Data<Vec<std::thread::ThreadId>>) {
+        for _ in 0..4 {
+            task! {
+                rt, record_thread,
+                RW: threads;
+            }
+        }
+    }
+
+    fn produce_message(message: &mut Option<String>) {
+        *message = Some(String::from("hello"));
+    }
+
+    fn consume_message(greeting: &i32, message: String) {
+        assert_eq!(*greeting, 42);
+        assert_eq!(message, "hello");
+    }
+
+    fn one_shot_flow(mut rt: Runtime, args: (Data<i32>, OnceData<String>)) {
+        let (mut greeting, mut message) = args;
+        task! {
+            rt, produce_message,
+            RW: message;
+        }
+        task! {
+            rt, consume_message,
+            R: greeting;
+            own: message;
+        }
+    }
+
+    fn phased_chain(mut rt: Runtime, mut a: Data<usize>) {
+        assert_eq!(rt.phase(), 0);
+        for phase in 1..=3 {
+            rt.begin_phase();
+            assert_eq!(rt.phase(), phase);
+            for _ in 0..10 {
+                task! {
+                    rt, increment,
+                    RW: a;
+                }
+            }
+        }
+        assert_eq!(rt.phase(), 3);
+    }
+
+    fn complex_mapping_flow(mut rt: Runtime<(usize, usize)>, mut a: Data<i32>) {
+        let coords = [(0, 1), (1, 1), (2, 1)];
+        for k in 0..coords.len() {
+            task! {
+                rt, double,
+                map: (coords[k].0, coords.get(k).map(|c| c.1).unwrap_or_default(),);
+                RW: a;
+            }
+        }
+        let check_is_doubled_thrice = |a: &i32| assert_eq!(*a, 21 * 8);
+        task! {
+            rt, check_is_doubled_thrice,
+            map: (0, coords.len().saturating_sub(3));
+            R: a;
+        }
+    }
+
+    fn set_one(_: &[&i32], writes: &mut [&mut i32]) {
+        *writes[0] = 1;
+    }
+
+    fn add_one(reads: &[&i32], writes: &mut [&mut i32]) {
+        *writes[0] = *reads[0] + 1;
+    }
+
+    fn times_ten(reads: &[&i32], writes: &mut [&mut i32]) {
+        *writes[0] = *reads[0] * 10;
+    }
+
+    fn sum(reads: &[&i32], writes: &mut [&mut i32]) {
+        *writes[0] = reads.iter().copied().sum();
+    }
+
     #[test]
     fn integration() {
         let map = move |task_id| ExecutorId::new((task_id as u32) % 2);
@@ -47,4 +433,901 @@ mod tests {
         let b = Data::new(20);
         go(2, map, (a, b), control_flow);
     }
+
+    #[test]
+    fn capturing_worker() {
+        let answer = 42;
+        let worker = move |mut rt: Runtime, mut a: Data<i32>| {
+            let set_answer = |a: &mut i32| *a = answer;
+            task! {
+                rt, set_answer,
+                RW: a;
+            }
+            task! {
+                rt, check_is_answer,
+                R: a;
+            }
+        };
+        go(2, get_round_robin_mapping(2), Data::new(0), worker);
+    }
+
+    #[test]
+    fn shared_args() {
+        let values = Arc::new(vec![0_u64; 1 << 20]);
+        let pointers = Mutex::new(Vec::new());
+        let worker = |_: Runtime, values: Arc<Vec<u64>>| {
+            pointers.lock().unwrap().push(values.as_ptr() as usize);
+        };
+        go_shared(4, get_round_robin_mapping(4), values.clone(), worker);
+
+        // All the threads see the same allocation
+        let pointers = pointers.into_inner().unwrap();
+        assert_eq!(pointers.len(), 4);
+        assert!(pointers.iter().all(|&ptr| ptr == values.as_ptr() as usize));
+    }
+
+    #[test]
+    fn skip_tasks() {
+        // The first three tasks are owned by the first executor, the other skips them as it never
+        // accesses `a`
+        let map = |task_id| ExecutorId::new(if task_id == 4 { 1 } else { 0 });
+        let next_ids = Mutex::new(Vec::new());
+        let worker = |mut rt: Runtime, thread_id: &mut u32, args: (Data<i32>, Data<i32>)| {
+            let (mut a, mut b) = args;
+            if *thread_id == 0 {
+                for _ in 0..3 {
+                    task! {
+                        rt, double,
+                        RW: a;
+                    }
+                }
+            } else {
+                unsafe { rt.skip_tasks(3) };
+            }
+            task! {
+                rt, double,
+                RW: b;
+            }
+            task! {
+                rt, check_is_answer,
+                R: b;
+            }
+            next_ids.lock().unwrap().push(unsafe { rt.next_task().0 });
+        };
+        go_with_tls(2, map, (Data::new(1), Data::new(21)), |id| id, worker);
+
+        let next_ids = next_ids.into_inner().unwrap();
+        assert_eq!(next_ids, vec![TaskId(6), TaskId(6)]);
+    }
+
+    #[test]
+    fn reset_between_computations() {
+        let map = move |task_id| ExecutorId::new((task_id as u32) % 2);
+        let mut a = Data::new(1);
+        let mut b = Data::new(20);
+        go(2, map, (a.clone(), b.clone()), control_flow);
+
+        // The task IDs of the second computation start from the beginning again
+        unsafe {
+            a.reset();
+            b.reset();
+        }
+        go(2, map, b, second_control_flow);
+    }
+
+    #[test]
+    fn short_dependencies() {
+        // Dependencies are likely satisfied while spinning
+        go(2, get_round_robin_mapping(2), Data::new(0), fast_chain);
+    }
+
+    #[test]
+    fn long_dependencies() {
+        // Dependencies are satisfied only once the waiting threads are parked
+        go(2, get_round_robin_mapping(2), Data::new(0), slow_chain);
+    }
+
+    #[test]
+    fn yield_if_not_owner() {
+        go(2, get_round_robin_mapping(2), Data::new(0), yielding_chain);
+    }
+
+    /// Attribute the tasks in contiguous blocks of 3, in a round robin fashion among 2 executors.
+    struct Blocks;
+
+    impl StatefulMapping for Blocks {
+        type State = usize;
+
+        fn init() -> usize {
+            0
+        }
+
+        fn map(nb_tasks: &mut usize, _: usize) -> ExecutorId {
+            let executor = ExecutorId::new(((*nb_tasks / 3) % 2) as u32);
+            *nb_tasks += 1;
+            executor
+        }
+    }
+
+    #[test]
+    fn stateful_mapping_blocks() {
+        let mut map = stateful_mapping::<Blocks, usize>();
+        let owners: Vec<u32> = (1..=8).map(|task| map(task).thread_id).collect();
+        assert_eq!(owners, vec![0, 0, 0, 1, 1, 1, 0, 0]);
+
+        // Copies start from a fresh state, while the original keeps its own
+        let mut copy = map.clone();
+        let owners: Vec<u32> = (1..=4).map(|task| copy(task).thread_id).collect();
+        assert_eq!(owners, vec![0, 0, 0, 1]);
+        let owners: Vec<u32> = (9..=10).map(|task| map(task).thread_id).collect();
+        assert_eq!(owners, vec![0, 1]);
+
+        go(2, stateful_mapping::<Blocks, usize>(), Data::new(0), fast_chain);
+    }
+
+    #[test]
+    fn no_lost_waiter() {
+        let sums = (0..8).map(|_| Data::new(0)).collect();
+        go(4, get_round_robin_mapping(4), (Data::new(0), sums), fan_out_fan_in);
+    }
+
+    #[test]
+    fn wait_until_write() {
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            unsafe {
+                *b.get_write(TaskId(5)) = 42;
+            }
+        });
+
+        a.wait_until(TaskId(5));
+        unsafe {
+            assert_eq!(*a.get_read(), 42);
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn slice() {
+        let tile = Data::from_boxed_slice(vec![0; 1000].into_boxed_slice());
+        go(2, get_round_robin_mapping(2), tile, slice_control_flow);
+    }
+
+    #[test]
+    fn two_buffers_stencil() {
+        let src = Data::from_boxed_slice(vec![0, 0, 0, 1, 0, 0, 0].into_boxed_slice());
+        let dst = Data::from_boxed_slice(vec![0; 7].into_boxed_slice());
+        go(2, get_round_robin_mapping(2), (src, dst), stencil);
+    }
+
+    #[test]
+    #[should_panic(expected = "RW_from: `a` and `b` are the same data")]
+    fn rw_from_same_data() {
+        let mut rt = Runtime::new(0, get_round_robin_mapping(1));
+        let mut a = Data::new(1);
+        let mut b = a.clone();
+        task! {
+            rt, add,
+            RW_from: a -> b;
+        }
+    }
+
+    #[test]
+    fn new_uninit() {
+        let a = unsafe { Data::<u64>::new_uninit() };
+        go(2, get_round_robin_mapping(2), a, uninit_flow);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+        assert_send_sync::<Data<i32>>();
+        assert_send_sync::<Data<[f64]>>();
+        assert_send_sync::<Data<Mutex<Vec<i32>>>>();
+    }
+
+    #[test]
+    fn next_task_owner() {
+        let map = |task_id: usize| ExecutorId::new((task_id * 7 % 5) as u32);
+        let mut rt = Runtime::new(2, map);
+        for task_id in 1..=20 {
+            let (_, owner) = unsafe { rt.next_task() };
+            assert_eq!(owner, Some(ExecutorId::new((task_id * 7 % 5) as u32)));
+        }
+        assert_eq!(rt.executor_id(), ExecutorId::new(2));
+    }
+
+    #[test]
+    fn tile_matrix() {
+        let matrix = TileMatrix::from_fn(3, 3, |_, _| 1);
+        go(2, get_round_robin_mapping(2), matrix, prefix_sums);
+    }
+
+    #[test]
+    fn disjoint_indexes() {
+        let mut values = [0, 1, 2, 3];
+        let [a, b, c] = disjoint_mut(&mut values, [3, 0, 2]);
+        assert_eq!((a, b, c), (Some(&mut 3), Some(&mut 0), Some(&mut 2)));
+    }
+
+    #[test]
+    fn same_indexes() {
+        let mut values = [0, 1, 2, 3];
+        let [a, b, c] = disjoint_mut(&mut values, [1, 1, 1]);
+        assert_eq!((a, b, c), (Some(&mut 1), None, None));
+    }
+
+    #[test]
+    fn overlapping_indexes() {
+        let mut values = [0, 1, 2, 3];
+        let [a, b, c, d] = disjoint_mut(&mut values, [2, 0, 2, 0]);
+        *a.unwrap() = 4;
+        assert_eq!((b, c, d), (Some(&mut 0), None, None));
+        assert_eq!(values, [0, 1, 4, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_bounds_index() {
+        let mut values = [0, 1, 2, 3];
+        disjoint_mut(&mut values, [0, 4]);
+    }
+
+    #[test]
+    fn cancellation() {
+        let token = CancellationToken::new();
+        let stops = Arc::new(Mutex::new(Vec::new()));
+        let args = (token.clone(), Data::new(0), stops.clone());
+        go_cancellable(4, get_round_robin_mapping(4), args, cancelled_flow, &token);
+
+        // All the executors stop at the same task, shortly after the cancellation
+        let stops = stops.lock().unwrap();
+        assert_eq!(stops.len(), 4);
+        assert!(stops.iter().all(|&stop| stop == stops[0]));
+        assert!(stops[0] >= 100 && stops[0] < 200);
+    }
+
+    #[test]
+    fn convergence() {
+        let last_residual = Mutex::new(1.0);
+        let stops = Arc::new(Mutex::new(Vec::new()));
+        let args = (Data::new(1.0), stops.clone());
+        let converged = go_until(
+            4,
+            get_round_robin_mapping(4),
+            args,
+            &last_residual,
+            converging_flow,
+            |residual| *residual.lock().unwrap() < 1e-3,
+        );
+        assert!(converged);
+
+        // The residual drops below the tolerance after 10 iterations, all the executors stop
+        // together shortly after
+        let stops = stops.lock().unwrap();
+        assert_eq!(stops.len(), 4);
+        assert!(stops.iter().all(|&stop| stop == stops[0]));
+        assert!(stops[0] >= 10 && stops[0] < 100);
+        assert_eq!(*last_residual.lock().unwrap(), 0.5f64.powi(stops[0] as i32));
+    }
+
+    #[test]
+    fn mapping_without_data() {
+        let owners = Arc::new(Mutex::new(Vec::new()));
+        go_with_tls(2, diagonal_mapping, owners.clone(), |id| id, record_owners);
+
+        let mut owners = owners.lock().unwrap();
+        owners.sort_unstable();
+        assert_eq!(*owners, vec![(0, 0, 0), (0, 1, 1), (1, 0, 1), (1, 1, 0)]);
+    }
+
+    #[test]
+    fn complex_mapping_expression() {
+        let a = Data::new(21);
+        go(2, diagonal_mapping, a, complex_mapping_flow);
+    }
+
+    #[test]
+    fn executor_local_storage() {
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        go_with_tls(2, get_round_robin_mapping(2), counts.clone(), |_| 0, count_owned);
+
+        // Each executor owned half of the tasks
+        assert_eq!(*counts.lock().unwrap(), vec![5, 5]);
+    }
+
+    #[test]
+    fn clone_inner() {
+        let mut a = Data::new(vec![0_u8; 4]);
+        let mut b = a.clone();
+        unsafe {
+            a.declare_write(TaskId(1));
+            b.get_write(TaskId(1))[0] = 1;
+        }
+        drop(b);
+        let mut copy = a.clone_inner();
+        unsafe {
+            copy.get_write(TaskId(1))[1] = 2;
+            assert_eq!(*copy.get_read(), vec![1, 2, 0, 0]);
+            // The copy is recorded as a read, the following writes wait for it
+            a.get_write(TaskId(2))[2] = 3;
+            assert_eq!(*a.get_read(), vec![1, 0, 3, 0]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Can not copy a shared data outside of a task")]
+    fn clone_inner_shared() {
+        let mut a = Data::new(0);
+        let _b = a.clone();
+        a.clone_inner();
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut a = Data::new(1);
+        let b = a.clone();
+        assert!(a.get_mut().is_none());
+        drop(b);
+        *a.get_mut().unwrap() = 2;
+        unsafe {
+            assert_eq!(*a.get_read(), 2);
+        }
+    }
+
+    #[test]
+    fn collect() {
+        let tiles = vec![Data::new(0), Data::new(0), Data::new(0)];
+        go(2, get_round_robin_mapping(2), tiles.clone(), |mut rt, tiles| {
+            for mut tile in tiles {
+                let write_one = |tile: &mut i32| *tile = 1;
+                task! {
+                    rt, write_one,
+                    RW: tile;
+                }
+            }
+        });
+        assert_eq!(collect_tiles(&tiles), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn write_chain() {
+        let sum = WriteChain::new(0i64);
+        go(4, get_round_robin_mapping(4), sum.clone(), |mut rt, mut sum| {
+            for x in 1..=100 {
+                sum.update(&mut rt, |acc| *acc += x);
+            }
+        });
+        assert_eq!(collect_tiles(&[sum.into_data()]), vec![5050]);
+    }
+
+    #[test]
+    #[should_panic(expected = "tile 1 is still shared by 1 other containers")]
+    fn collect_shared_tile() {
+        let tiles = vec![Data::new(0), Data::new(0)];
+        let _copy = tiles[1].clone();
+        collect_tiles(&tiles);
+    }
+
+    #[test]
+    fn weak_data() {
+        let a = Data::new(1);
+        let b = a.clone();
+        let weak = a.downgrade();
+        let observer = weak.upgrade().unwrap();
+        assert!(Data::ptr_eq(&observer, &a));
+        assert_eq!(observer.try_read_latest(), Some(1));
+        drop(observer);
+
+        // Weak handles neither keep the data alive nor prevent taking the value
+        drop(b);
+        assert_eq!(a.into_inner().ok(), Some(1));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn task_graph() {
+        let mut graph = TaskGraph::new();
+        let a = graph.add_data(0);
+        let b = graph.add_data(0);
+        let c = graph.add_data(0);
+        let d = graph.add_data(0);
+        let top = graph.add_task(set_one, &[], &[a]);
+        let left = graph.add_task(add_one, &[a], &[b]);
+        let right = graph.add_task(times_ten, &[a], &[c]);
+        let bottom = graph.add_task(sum, &[b, c], &[d]);
+        assert_eq!(graph.dependencies(left), &[top]);
+        assert_eq!(graph.dependencies(right), &[top]);
+        assert_eq!(graph.dependencies(bottom), &[left, right]);
+
+        // The graph can be replayed
+        for _ in 0..2 {
+            execute(&mut graph, 2, get_round_robin_mapping(2));
+            assert_eq!(*graph.get_mut(d), 12);
+        }
+    }
+
+    #[test]
+    fn replay_trace() {
+        let path = std::env::temp_dir().join("rio_replay_trace.csv");
+        std::fs::write(&path, "task_id,executor_id\n1,1\n2,1\n3,0,10,20\n").unwrap();
+        let mut map = trace_mapping(&path, 2).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let executors: Vec<u32> = (1..=5).map(|task_id| map(task_id).thread_id).collect();
+        assert_eq!(executors, vec![1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn trace() {
+        let mut trace = Vec::new();
+        go_traced(2, get_round_robin_mapping(2), Data::new(0), fast_chain, &mut trace).unwrap();
+
+        // One record per task, in addition to the header
+        let trace = String::from_utf8(trace).unwrap();
+        let records: Vec<&str> = trace.lines().skip(1).collect();
+        assert_eq!(records.len(), 1001);
+        for (idx, record) in records.iter().enumerate() {
+            let fields: Vec<u64> = record.split(',').map(|f| f.parse().unwrap()).collect();
+            assert_eq!(fields[0], idx as u64 + 1);
+            assert_eq!(fields[1], fields[0] % 2);
+            assert!(fields[2] <= fields[3]);
+        }
+    }
+
+    #[test]
+    fn try_read_latest() {
+        let a = Data::new(0_u64);
+        let mut b = a.clone();
+        assert_eq!(a.try_read_latest(), Some(0));
+        for task in 1..=3 {
+            unsafe {
+                let mut write = b.get_write(TaskId(task));
+                *write = task as u64;
+                // The write is in progress
+                assert_eq!(a.try_read_latest(), None);
+            }
+            assert_eq!(a.try_read_latest(), Some(task as u64));
+        }
+    }
+
+    #[test]
+    fn single_root() {
+        let roots = Arc::new(Mutex::new(0));
+        go(4, get_round_robin_mapping(4), roots.clone(), |rt, roots| {
+            if rt.is_root() {
+                *roots.lock().unwrap() += 1;
+            }
+            rt.print_once("Printed by a single executor");
+        });
+        assert_eq!(*roots.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn all_executors() {
+        let ids: Vec<u32> = ExecutorId::all(3).map(u32::from).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert!(ExecutorId::from(2) == ExecutorId::new(2));
+        assert_eq!(ExecutorId::all(0).count(), 0);
+    }
+
+    #[test]
+    fn seeded_per_executor() {
+        let run = || {
+            let draws = Arc::new(Mutex::new(Vec::new()));
+            go(2, get_round_robin_mapping(2), draws.clone(), draw_random);
+            let mut draws = draws.lock().unwrap().clone();
+            draws.sort();
+            draws
+        };
+        let draws = run();
+        assert_eq!(draws.len(), 2);
+        assert_ne!(draws[0].1, draws[1].1);
+        assert_eq!(draws, run());
+    }
+
+    #[test]
+    fn phase() {
+        let mut trace = Vec::new();
+        go_traced(2, get_round_robin_mapping(2), Data::new(0), phased_chain, &mut trace).unwrap();
+
+        // The tasks are tagged with the phase they were issued in
+        let trace = String::from_utf8(trace).unwrap();
+        let phases: Vec<u64> = trace
+            .lines()
+            .skip(1)
+            .map(|record| record.split(',').nth(4).unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(phases.len(), 30);
+        for (idx, phase) in phases.iter().enumerate() {
+            assert_eq!(*phase, idx as u64 / 10 + 1);
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut threads = Data::new(Vec::new());
+        go_round_robin(2, threads.clone(), thread_chain);
+
+        // The tasks alternate between the two executors
+        let threads = threads.get_mut().unwrap();
+        assert_eq!(threads.len(), 4);
+        assert_ne!(threads[0], threads[1]);
+        assert_eq!(threads[0], threads[2]);
+        assert_eq!(threads[1], threads[3]);
+    }
+
+    #[test]
+    fn once_data() {
+        let mut message = OnceData::new();
+        let args = (Data::new(42), message.clone());
+        go(2, get_round_robin_mapping(2), args, one_shot_flow);
+
+        // The consumer took the value
+        assert_eq!(message.version(), 2);
+        assert_eq!(message.get_mut(), Some(&mut None));
+    }
+
+    #[test]
+    fn new_vec() {
+        let mut a = Data::<Vec<u64>>::new_vec(100);
+        unsafe {
+            let mut tile = a.get_write(TaskId(1));
+            assert!(tile.capacity() >= 100);
+            assert!(tile.is_empty());
+            let buffer = tile.as_ptr();
+            tile.extend(0..100);
+            // Filling the tile did not reallocate it
+            assert_eq!(tile.as_ptr(), buffer);
+        }
+    }
+
+    #[test]
+    fn version() {
+        let mut a = Data::new(0);
+        assert_eq!(a.version(), 0);
+        for task_id in 1..=3 {
+            unsafe {
+                *a.get_write(TaskId(task_id)) += 1;
+            }
+        }
+        assert_eq!(a.version(), 3);
+    }
+
+    #[test]
+    fn downgrade() {
+        let mut a = Data::new(1);
+        let b = a.clone();
+        unsafe {
+            let mut write = a.get_write(TaskId(1));
+            *write = 2;
+            let read = write.downgrade();
+            assert_eq!(*read, 2);
+            // The write is not terminated yet
+            assert_eq!(b.version(), 0);
+            let mut write = read.try_upgrade(TaskId(1)).ok().unwrap();
+            *write = 3;
+        }
+        assert_eq!(b.version(), 1);
+    }
+
+    #[test]
+    fn failed_upgrade() {
+        let mut a = Data::new(1);
+        unsafe {
+            let read = a.get_read();
+            let read = read.try_upgrade(TaskId(1)).err().unwrap();
+            assert_eq!(*read, 1);
+        }
+        assert_eq!(a.version(), 0);
+    }
+
+    #[test]
+    fn sync_core() {
+        use crate::sync_core::{wait_for, wake_up, Awaited, LocalState, Lock, LockedState};
+        use std::cell::{Cell, RefCell};
+
+        /// A single-threaded lock: nobody else can run while a thread is parked, the writes
+        /// executed by other executors are replayed when waiting instead.
+        struct MockLock {
+            state: RefCell<LockedState>,
+            pending_writes: RefCell<Vec<usize>>,
+            nb_notified: Cell<usize>,
+        }
+
+        impl Lock for MockLock {
+            type Guard<'a> = std::cell::RefMut<'a, LockedState>;
+
+            fn lock(&self) -> Self::Guard<'_> {
+                self.state.borrow_mut()
+            }
+
+            fn wait<'a>(&'a self, mut guard: Self::Guard<'a>) -> Self::Guard<'a> {
+                let task_id = self.pending_writes.borrow_mut().remove(0);
+                guard.start_write();
+                guard.execute_write(task_id);
+                guard
+            }
+
+            fn notify_all(&self) {
+                self.nb_notified.set(self.nb_notified.get() + 1);
+            }
+        }
+
+        let lock = MockLock {
+            state: RefCell::new(LockedState::new()),
+            pending_writes: RefCell::new(vec![1]),
+            nb_notified: Cell::new(0),
+        };
+        let mut writer = LocalState::new();
+        let mut reader = LocalState::new();
+
+        // The first write is ready right away, but the read must wait for it
+        assert!(writer.awaited_write().is_satisfied(&lock.lock()));
+        writer.declare_write(1);
+        reader.declare_write(1);
+        assert!(reader.is_dirty());
+        let awaited = reader.awaited_read();
+        let mut state = lock.lock();
+        assert!(!awaited.is_satisfied(&state));
+        state = wait_for(&lock, state, awaited);
+        assert_eq!(state.last_executed_write(), 1);
+        assert!(lock.pending_writes.borrow().is_empty());
+
+        // The next write waits for the read
+        writer.declare_read();
+        let awaited = writer.awaited_write();
+        assert!(!awaited.is_satisfied(&state));
+        state.execute_read();
+        reader.terminate_read();
+        assert!(!reader.is_dirty());
+        assert!(awaited.is_satisfied(&state));
+        writer.declare_write(3);
+
+        // Parked threads are only woken up once they can make progress
+        state.waiters.push(Awaited::AtLeast { write: 3 });
+        wake_up(&lock, &state);
+        assert_eq!(lock.nb_notified.get(), 0);
+        state.start_write();
+        assert!(state.is_writing());
+        state.execute_write(3);
+        wake_up(&lock, &state);
+        assert_eq!(lock.nb_notified.get(), 1);
+    }
+
+    #[test]
+    fn panic_while_writing() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        let reader = std::thread::spawn(move || unsafe {
+            b.declare_write(TaskId(1));
+            // Parks until the write terminates, then observes the poisoning
+            let read = panic::catch_unwind(AssertUnwindSafe(|| *b.get_read()));
+            (read.is_err(), b.is_poisoned())
+        });
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let write = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            let mut a = a.get_write(TaskId(1));
+            *a = 1;
+            panic!("task failed");
+        }));
+        assert!(write.is_err());
+        assert!(a.is_poisoned());
+        assert_eq!(reader.join().unwrap(), (true, true));
+
+        // The poisoning is cleared by a reset
+        unsafe { a.reset() };
+        assert!(!a.is_poisoned());
+    }
+
+    #[test]
+    fn panic_while_locked() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::mpsc;
+
+        // Hold the lock of the data from another thread for a while, e.g. while a task panics
+        fn hold_lock(data: Data<i32>) {
+            let (locked, is_locked) = mpsc::channel();
+            std::thread::spawn(move || {
+                let _guard = data.lock_shared_state();
+                locked.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            });
+            is_locked.recv().unwrap();
+        }
+
+        // A panicking write still poisons the data, the parked reader observes it
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        let c = a.clone();
+        let reader = std::thread::spawn(move || unsafe {
+            b.declare_write(TaskId(1));
+            panic::catch_unwind(AssertUnwindSafe(|| *b.get_read())).is_err()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let write = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            let _a = a.get_write(TaskId(1));
+            hold_lock(c);
+            panic!("task failed");
+        }));
+        assert!(write.is_err());
+        assert!(reader.join().unwrap());
+
+        // A panicking read is still recorded, the parked writer proceeds
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        let c = a.clone();
+        let writer = std::thread::spawn(move || unsafe {
+            b.declare_read();
+            *b.get_write(TaskId(1)) = 1;
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let read = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            let _a = a.get_read();
+            hold_lock(c);
+            panic!("task failed");
+        }));
+        assert!(read.is_err());
+        writer.join().unwrap();
+        assert!(!a.is_poisoned());
+    }
+
+    #[test]
+    fn committed_reads() {
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        unsafe {
+            a.declare_write(TaskId(1));
+            assert!(a.is_locally_dirty());
+            *b.get_write(TaskId(1)) = 1;
+            assert!(!b.is_locally_dirty());
+            assert_eq!(a.committed_reads(), 0);
+
+            // The reads are declared by `a` and executed by `b`
+            a.declare_read();
+            a.declare_read();
+            assert_eq!(a.committed_reads(), 0);
+            drop(b.get_read());
+            drop(b.get_read());
+        }
+        assert_eq!(a.committed_reads(), 2);
+        assert!(a.is_locally_dirty());
+        assert!(!b.is_locally_dirty());
+    }
+
+    #[test]
+    fn max_in_flight() {
+        // A single slow owner executes all the tasks, the other executor only declares them
+        let map = |_| ExecutorId::new(0);
+        let max_lead = Arc::new(Mutex::new(0));
+        go(2, map, (Data::new(0), max_lead.clone()), bounded_flow);
+        assert!(*max_lead.lock().unwrap() <= 4);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-checks")]
+    #[should_panic(expected = "Executors issued different numbers of tasks (T0: 2, T1: 1)")]
+    fn diverging_task_counts() {
+        let worker = |mut rt: Runtime, thread_id: &mut u32, mut a: Data<i32>| {
+            task! {
+                rt, double,
+                RW: a;
+            }
+            if *thread_id == 0 {
+                // Not accounted for by the other executor
+                task! {
+                    rt, check_is_answer,
+                    R: a;
+                }
+            }
+        };
+        go_with_tls(2, |_| ExecutorId::new(0), Data::new(21), |id| id, worker);
+    }
+
+    mod prelude {
+        // Only the prelude is imported
+        use crate::prelude::*;
+
+        fn double(a: &mut i32) {
+            *a *= 2;
+        }
+
+        fn check_is_answer(a: &i32) {
+            assert_eq!(*a, 42);
+        }
+
+        fn program(mut rt: Runtime, mut a: Data<i32>) {
+            task! {
+                rt, double,
+                RW: a;
+            }
+            task! {
+                rt, check_is_answer,
+                R: a;
+            }
+        }
+
+        #[test]
+        fn prelude() {
+            go(2, get_round_robin_mapping(2), Data::new(21), program);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn debug_name() {
+        struct Capture(Mutex<Vec<String>>);
+
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        fn named_chain(mut rt: Runtime, mut a: Data<usize>) {
+            for _ in 0..2 {
+                task! {
+                    rt, increment,
+                    debug_name: "increment";
+                    RW: a;
+                }
+            }
+            // Unnamed tasks are not logged
+            task! {
+                rt, increment,
+                RW: a;
+            }
+        }
+
+        static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+        go(2, get_round_robin_mapping(2), Data::new(0), named_chain);
+
+        let mut messages = LOGGER.0.lock().unwrap().clone();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "increment: task 1 -> executor 1, owner=false",
+                "increment: task 1 -> executor 1, owner=true",
+                "increment: task 2 -> executor 0, owner=false",
+                "increment: task 2 -> executor 0, owner=true",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn contention_stats() {
+        let mut a = Data::new(0);
+        let mut b = a.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            unsafe {
+                *b.get_write(TaskId(1)) = 42;
+            }
+        });
+
+        // The read must wait for the write to be executed
+        unsafe {
+            a.declare_write(TaskId(1));
+            assert_eq!(*a.get_read(), 42);
+        }
+        writer.join().unwrap();
+        let stats = a.contention_stats();
+        assert_eq!(stats.times_blocked, 1);
+        assert!(stats.total_wait_ns > 0);
+    }
 }
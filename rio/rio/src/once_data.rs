@@ -0,0 +1,73 @@
+//! # One-Shot Data
+//!
+//! Some tasks consume a value exactly once, e.g. a work item handed from one task to the next,
+//! rather than borrowing a shared data. A `OnceData` holds such a value: a task produces it, and a
+//! later task takes it by value through the `own:` section of the `task!` macro.
+
+use super::data::{Data, TaskId};
+use std::ops::{Deref, DerefMut};
+
+/// A value produced by a write task and moved out by a single later task.
+///
+/// The producer sets the value with a regular write (`RW:` section), the consumer takes it in the
+/// `own:` section. Taking the value is a write, hence the consumer runs after the producer. As for
+/// `Data`, each executor works on its own clone, and the executors not owning the consumer only
+/// declare the task.
+pub struct OnceData<T> {
+    data: Data<Option<T>>,
+}
+
+impl<T> OnceData<T> {
+    /// Create an empty cell, waiting for its producer.
+    pub fn new() -> Self {
+        Self {
+            data: Data::new(None),
+        }
+    }
+
+    /// Move the value out of the cell as the write task `task_id`, this is used by the `task!`
+    /// macro.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value has not been produced, or if it has already been taken.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Data::get_write`, this function should be used through the `task!` macro.
+    pub unsafe fn take(&mut self, task_id: TaskId) -> T {
+        self.data
+            .get_write(task_id)
+            .take()
+            .expect("OnceData: the value is taken before being produced, or taken twice")
+    }
+}
+
+impl<T> Default for OnceData<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for OnceData<T> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+        }
+    }
+}
+
+/// The producer accesses the cell as a `Data<Option<T>>`.
+impl<T> Deref for OnceData<T> {
+    type Target = Data<Option<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for OnceData<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
@@ -0,0 +1,13 @@
+//! # Prelude
+//!
+//! The items needed by most programs, including the `task!` macro:
+//!
+//! ```ignore
+//! use rio::prelude::*;
+//! ```
+
+pub use crate::task;
+pub use crate::{
+    get_round_robin_mapping, go, go_round_robin, stateful_mapping, trace_mapping, Data, ExecutorId,
+    Mapping, Runtime, StatefulMapping, TaskId,
+};
@@ -1,9 +1,18 @@
-use super::data::TaskId;
+use super::data::{Data, TaskId};
 use crossbeam::thread;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 // ————————————————————————————————— Runtime ———————————————————————————————— //
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ExecutorId {
     pub thread_id: u32,
 }
@@ -12,6 +21,23 @@ impl ExecutorId {
     pub fn new(thread_id: u32) -> Self {
         Self { thread_id }
     }
+
+    /// Iterate over the executors of a computation running on `nb_threads` threads.
+    pub fn all(nb_threads: u32) -> impl Iterator<Item = ExecutorId> {
+        (0..nb_threads).map(ExecutorId::new)
+    }
+}
+
+impl From<u32> for ExecutorId {
+    fn from(thread_id: u32) -> Self {
+        Self::new(thread_id)
+    }
+}
+
+impl From<ExecutorId> for u32 {
+    fn from(executor_id: ExecutorId) -> Self {
+        executor_id.thread_id
+    }
 }
 
 /// A trait representing a mapping, automatically implemented for closures satisfying the
@@ -26,11 +52,89 @@ pub trait Mapping<Args = usize>: FnMut(Args) -> ExecutorId + Send {}
 
 impl<T, Args> Mapping<Args> for T where T: FnMut(Args) -> ExecutorId + Send {}
 
+/// A mapping relying on some state, which evolves as tasks are attributed (e.g. a counter).
+///
+/// Each executor starts from a fresh state created by `init`, and the state only evolves through
+/// `map`: as long as `map` is deterministic all the executors agree on the attribution of the
+/// tasks. Use `stateful_mapping` to turn a stateful mapping into a `Mapping`.
+pub trait StatefulMapping<Args = usize> {
+    type State: Send;
+
+    /// Create the initial state, it must be the same for all executors.
+    fn init() -> Self::State;
+
+    /// Attribute a task, given the state of the mapping.
+    fn map(state: &mut Self::State, args: Args) -> ExecutorId;
+}
+
+/// Create a mapping from a stateful mapping.
+///
+/// Each copy of the mapping (one per executor) starts from a fresh state, the state itself is
+/// never cloned.
+pub fn stateful_mapping<M, Args>() -> impl Mapping<Args> + Clone
+where
+    M: StatefulMapping<Args>,
+{
+    let mut state = FreshState::<M, Args>(None, PhantomData);
+    move |args| {
+        let state = state.0.get_or_insert_with(M::init);
+        M::map(state, args)
+    }
+}
+
+/// The state of a stateful mapping, lazily initialized and reset when cloned.
+struct FreshState<M: StatefulMapping<Args>, Args>(Option<M::State>, PhantomData<fn(Args) -> M>);
+
+impl<M: StatefulMapping<Args>, Args> Clone for FreshState<M, Args> {
+    fn clone(&self) -> Self {
+        FreshState(None, PhantomData)
+    }
+}
+
 /// Create a simple mapping, attributing tasks among `nb_threads` in a round robin fashion.
 pub fn get_round_robin_mapping(nb_threads: u32) -> impl Mapping + Clone {
-    move |task_id: usize| ExecutorId {
-        thread_id: ((task_id) % (nb_threads as usize)) as u32,
+    move |task_id: usize| ExecutorId::from((task_id % nb_threads as usize) as u32)
+}
+
+/// Create a mapping replaying the schedule recorded in the file at `path`.
+///
+/// Each line of the file holds a `task_id,executor_id` pair, any following field is ignored so
+/// that the traces emitted by `go_traced` can be replayed directly. Tasks absent from the file are
+/// attributed among `nb_threads` in a round robin fashion.
+pub fn trace_mapping(path: impl AsRef<Path>, nb_threads: u32) -> io::Result<impl Mapping + Clone> {
+    let mut schedule: HashMap<usize, ExecutorId> = HashMap::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.trim().is_empty() || line.starts_with("task_id") {
+            // Skip empty lines and header
+            continue;
+        }
+        let mut fields = line.split(',').map(|field| field.trim().parse());
+        match (fields.next(), fields.next()) {
+            (Some(Ok(task_id)), Some(Ok(thread_id))) => {
+                schedule.insert(task_id, ExecutorId::new(thread_id as u32));
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid trace line: '{}'", line),
+                ))
+            }
+        }
     }
+
+    let schedule = Arc::new(schedule);
+    Ok(move |task_id: usize| match schedule.get(&task_id) {
+        Some(executor_id) => *executor_id,
+        None => ExecutorId::new((task_id % nb_threads as usize) as u32),
+    })
+}
+
+/// The SplitMix64 mixing function, which maps consecutive integers to well distributed values.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
 /// A thread-local data structure used to decide what tasks to execute on that thread.
@@ -38,14 +142,25 @@ pub struct Runtime<'map, Args = usize> {
     executor_id: ExecutorId,
     task_counter: usize,
     map: Box<dyn Mapping<Args> + 'map>,
+    cancellation: Option<Cancellation>,
+    /// Cancel the computation once it returns true, checked before issuing each task.
+    stop_condition: Option<Box<dyn Fn() -> bool + Send + 'map>>,
+    max_in_flight: Option<usize>,
+    yield_if_not_owner: bool,
+    /// The number of phases begun, which tags the traced tasks.
+    phase: usize,
+    tracer: Option<Tracer>,
+    /// Name of the next task, whose ownership decision is logged.
+    #[cfg(feature = "log")]
+    debug_name: Option<String>,
+    /// Where to report the number of issued tasks once the executor terminates.
+    #[cfg(feature = "debug-checks")]
+    task_counts: Option<TaskCounts>,
 }
 
-/// Represents the ownership of a task.
-/// The owner is the only thread that has to execute the task.
-pub enum TaskOwnership {
-    Owner,
-    NotOwner,
-}
+/// The number of tasks issued by each executor, by thread ID.
+#[cfg(feature = "debug-checks")]
+type TaskCounts = Arc<Mutex<Vec<(u32, usize)>>>;
 
 impl<'map, Args> Runtime<'map, Args> {
     pub fn new(thread_id: u32, map: impl Mapping<Args> + 'map) -> Self {
@@ -53,56 +168,615 @@ impl<'map, Args> Runtime<'map, Args> {
             executor_id: ExecutorId { thread_id },
             task_counter: 0,
             map: Box::new(map),
+            cancellation: None,
+            stop_condition: None,
+            max_in_flight: None,
+            yield_if_not_owner: false,
+            phase: 0,
+            tracer: None,
+            #[cfg(feature = "log")]
+            debug_name: None,
+            #[cfg(feature = "debug-checks")]
+            task_counts: None,
+        }
+    }
+
+    /// Bound the number of tasks a thread can declare ahead of the execution.
+    ///
+    /// When declaring a task it does not own, the thread blocks until the data accessed by the
+    /// task are at most `max_in_flight` tasks behind. `None` (the default) means no bound.
+    pub fn set_max_in_flight(&mut self, max_in_flight: Option<usize>) {
+        self.max_in_flight = max_in_flight;
+    }
+
+    /// Return the bound on the number of tasks declared ahead of the execution, if any.
+    pub fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    /// Yield the thread after declaring a task it does not own, giving CPU time to the owners
+    /// instead of running ahead of them. Disabled by default.
+    pub fn set_yield_if_not_owner(&mut self, yield_if_not_owner: bool) {
+        self.yield_if_not_owner = yield_if_not_owner;
+    }
+
+    /// Return true if the thread yields after declaring a task it does not own.
+    pub fn yield_if_not_owner(&self) -> bool {
+        self.yield_if_not_owner
+    }
+
+    /// Name the next task, this is used by the `task!` macro.
+    ///
+    /// With the `log` feature, the mapping of the next task and whether the current thread owns it
+    /// are logged at trace level. Without it this is a no-op.
+    pub fn set_debug_name(&mut self, name: &str) {
+        #[cfg(feature = "log")]
+        {
+            self.debug_name = Some(name.to_string());
         }
+        #[cfg(not(feature = "log"))]
+        let _ = name;
+    }
+
+    /// Begin a new phase of the computation, e.g. at the top of each iteration of an iterative
+    /// computation. The traced tasks are tagged with their phase, so that tools can group them.
+    ///
+    /// All the executors must call this function the same number of times and at the same points
+    /// of the computation, otherwise the phases of the tasks disagree from one executor to another.
+    pub fn begin_phase(&mut self) {
+        self.phase += 1;
+    }
+
+    /// Return the current phase, that is the number of calls to `begin_phase` so far.
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    /// Return the executor running on this thread, the owner of a task is the only thread that
+    /// has to execute it.
+    pub fn executor_id(&self) -> ExecutorId {
+        self.executor_id
     }
 
-    /// Given the arguments to the mapping function, return the next task ID and wether the current
-    /// thread has ownership of the task.
+    /// Derive a seed specific to this executor from `base_seed`, e.g. to seed a random number
+    /// generator per thread: the executors get distinct seeds, but the same ones from one run to the
+    /// next.
+    ///
+    /// Random values used to map tasks must be identical on all the executors, they must be drawn
+    /// from a generator seeded with `base_seed` instead.
+    pub fn seeded_per_executor(&self, base_seed: u64) -> u64 {
+        base_seed ^ splitmix64(self.executor_id.thread_id as u64)
+    }
+
+    /// Return true for exactly one executor of the computation, the one running on thread 0.
+    ///
+    /// This is a convenience to pick a single writer, e.g. to print a summary once instead of once
+    /// per thread.
+    pub fn is_root(&self) -> bool {
+        self.executor_id.thread_id == 0
+    }
+
+    /// Print `message` on the standard output from the root executor only.
+    pub fn print_once(&self, message: impl fmt::Display) {
+        if self.is_root() {
+            println!("{}", message);
+        }
+    }
+
+    /// Stop issuing tasks once `token` is cancelled.
+    fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(Cancellation {
+            token,
+            reported: false,
+        });
+        self
+    }
+
+    /// Cancel the computation once `stop_condition` returns true, requires a cancellation token.
+    fn with_stop_condition(mut self, stop_condition: impl Fn() -> bool + Send + 'map) -> Self {
+        self.stop_condition = Some(Box::new(stop_condition));
+        self
+    }
+
+    /// Record the tasks owned by this thread into `sink`.
+    fn with_tracer(mut self, sink: Arc<TraceSink>) -> Self {
+        self.tracer = Some(Tracer {
+            sink,
+            records: Vec::new(),
+        });
+        self
+    }
+
+    /// Report the number of issued tasks into `task_counts` when the runtime is dropped.
+    #[cfg(feature = "debug-checks")]
+    fn with_task_counts(mut self, task_counts: TaskCounts) -> Self {
+        self.task_counts = Some(task_counts);
+        self
+    }
+
+    /// Return the start time of an owned task if the computation is traced, this is used by the
+    /// `task!` macro.
+    pub fn trace_start(&self) -> Option<Instant> {
+        self.tracer.as_ref().map(|_| Instant::now())
+    }
+
+    /// Record the execution of an owned task which started at `start`, this is used by the
+    /// `task!` macro.
+    pub fn trace_end(&mut self, task_id: TaskId, start: Option<Instant>) {
+        if let (Some(tracer), Some(start)) = (&mut self.tracer, start) {
+            let epoch = tracer.sink.epoch;
+            tracer.records.push(TaskRecord {
+                task_id: task_id.0,
+                executor_id: self.executor_id.thread_id,
+                phase: self.phase,
+                start_ns: start.duration_since(epoch).as_nanos() as u64,
+                end_ns: epoch.elapsed().as_nanos() as u64,
+            });
+        }
+    }
+
+    /// Return true if the computation has been cancelled, in which case all the following tasks
+    /// are skipped.
+    pub fn is_cancelled(&mut self) -> bool {
+        let next_task = self.task_counter + 1;
+        match &mut self.cancellation {
+            Some(cancellation) => {
+                if let Some(stop_condition) = &self.stop_condition {
+                    if !cancellation.token.is_cancelled() && stop_condition() {
+                        cancellation.token.cancel();
+                    }
+                }
+                cancellation.is_cancelled_at(next_task)
+            }
+            None => false,
+        }
+    }
+
+    /// Advance the task counter by `n` tasks, without declaring any data access.
+    ///
+    /// This allows executors to stay in lockstep when some of them skip a region of tasks, e.g.
+    /// because of a branch taken only by the owner of the region.
+    ///
+    /// # Safety
+    ///
+    /// Every executor must account for the same number of tasks: the ones that do not issue the
+    /// skipped tasks must call this function with the exact number of tasks issued by the others.
+    /// The skipped tasks must not access data used by the executors skipping them.
+    pub unsafe fn skip_tasks(&mut self, n: usize) {
+        self.task_counter += n;
+    }
+
+    /// Given the arguments to the mapping function, return the next task ID and the executor
+    /// owning the task, or `None` if the computation has been cancelled in which case the task must
+    /// not be executed by any thread.
+    ///
+    /// The current thread has ownership of the task if the owner is its `executor_id`.
     ///
     /// # Safety
     ///
     /// This function or `next_task` should be called exactly once per task (for each thread).
     /// Instead of calling the function directly, the `task!` macro is provided to ensure correct
     /// usage.
-    pub unsafe fn next_task_args(&mut self, args: Args) -> (TaskId, TaskOwnership) {
+    pub unsafe fn next_task_args(&mut self, args: Args) -> (TaskId, Option<ExecutorId>) {
+        #[cfg(feature = "log")]
+        let debug_name = self.debug_name.take();
+        if self.is_cancelled() {
+            return (TaskId(self.task_counter + 1), None);
+        }
         self.task_counter += 1;
         let task_id = TaskId(self.task_counter);
-        let ownership = if (self.map)(args) == self.executor_id {
-            TaskOwnership::Owner
-        } else {
-            TaskOwnership::NotOwner
-        };
-
-        (task_id, ownership)
+        let executor_id = (self.map)(args);
+        #[cfg(feature = "log")]
+        if let Some(name) = debug_name {
+            log::trace!(
+                "{}: task {} -> executor {}, owner={}",
+                name,
+                task_id.0,
+                executor_id.thread_id,
+                executor_id == self.executor_id
+            );
+        }
+        (task_id, Some(executor_id))
     }
 }
 
-impl <'map> Runtime<'map, usize> {
-    /// Return the next task ID and whether the current thread has ownership of the task.
+impl<'map> Runtime<'map, usize> {
+    /// Return the next task ID and the executor owning the task, or `None` if the computation has
+    /// been cancelled.
     ///
     /// # Safety
     ///
     /// This function or `next_task_args` should be called exactly once per task (for each thread).
     /// Instead of calling the function directly, the `task!` macro is provided to ensure correct
     /// usage.
-    pub unsafe fn next_task(&mut self) -> (TaskId, TaskOwnership) {
+    pub unsafe fn next_task(&mut self) -> (TaskId, Option<ExecutorId>) {
         self.next_task_args(self.task_counter + 1)
     }
+
+    /// Issue the next task, reading `reads` and writing `writes`, the same way as the `task!` macro:
+    /// the owner executes the task by calling `execute` with the task ID and the data, the other
+    /// executors declare the accesses, subject to `max_in_flight` and `yield_if_not_owner`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `next_task`, and `execute` must access the data as declared: it reads `reads` and
+    /// writes `writes` as the task `task_id`.
+    pub(crate) unsafe fn issue_task<T, F>(
+        &mut self,
+        reads: &mut [&mut Data<T>],
+        writes: &mut [&mut Data<T>],
+        execute: F,
+    ) where
+        T: ?Sized,
+        F: FnOnce(TaskId, &mut [&mut Data<T>], &mut [&mut Data<T>]),
+    {
+        let (task_id, owner) = self.next_task();
+        match owner {
+            Some(owner) if owner == self.executor_id => {
+                let start = self.trace_start();
+                execute(task_id, reads, writes);
+                self.trace_end(task_id, start);
+            }
+            Some(_) => {
+                if let Some(max_in_flight) = self.max_in_flight {
+                    for data in reads.iter().chain(writes.iter()) {
+                        data.wait_in_flight(task_id, max_in_flight);
+                    }
+                }
+                for data in reads.iter_mut() {
+                    data.declare_read();
+                }
+                for data in writes.iter_mut() {
+                    data.declare_write(task_id);
+                }
+                if self.yield_if_not_owner {
+                    std::thread::yield_now();
+                }
+            }
+            // The computation has been cancelled
+            None => {}
+        }
+    }
+}
+
+#[cfg(feature = "debug-checks")]
+impl<'map, Args> Drop for Runtime<'map, Args> {
+    fn drop(&mut self) {
+        if let Some(task_counts) = &self.task_counts {
+            let mut task_counts = task_counts.lock().unwrap();
+            task_counts.push((self.executor_id.thread_id, self.task_counter));
+        }
+    }
+}
+
+// —————————————————————————————— Cancellation —————————————————————————————— //
+
+/// A token used to cancel a computation started with `go_cancellable`.
+///
+/// Executors observe the cancellation at different times, but they must all stop at the same task
+/// to avoid waiting for a task that its owner skipped. Once the cancellation is requested, each
+/// executor raises a shared boundary above every task it issues, until all the executors observed
+/// the cancellation: from then on the boundary is final, and the tasks after it are skipped.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Arc<CancellationState>,
+}
+
+struct CancellationState {
+    cancelled: AtomicBool,
+    nb_executors: AtomicUsize,
+    boundary: Mutex<CancellationBoundary>,
+}
+
+struct CancellationBoundary {
+    // The first task to skip, once final
+    first_skipped: usize,
+    nb_reported: usize,
+    is_final: bool,
+}
+
+/// The state of the cancellation, as seen by an executor.
+struct Cancellation {
+    token: CancellationToken,
+    reported: bool,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(CancellationState {
+                cancelled: AtomicBool::new(false),
+                nb_executors: AtomicUsize::new(0),
+                boundary: Mutex::new(CancellationBoundary {
+                    first_skipped: 0,
+                    nb_reported: 0,
+                    is_final: false,
+                }),
+            }),
+        }
+    }
+
+    /// Request the cancellation of the computation, the executors stop issuing tasks shortly
+    /// after.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Return true if the cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cancellation {
+    /// Return true if `task` must be skipped.
+    fn is_cancelled_at(&mut self, task: usize) -> bool {
+        let state = &self.token.state;
+        if !state.cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        let mut boundary = state.boundary.lock().unwrap();
+        if !boundary.is_final {
+            // The task might be issued, it must be before the boundary
+            boundary.first_skipped = boundary.first_skipped.max(task + 1);
+            if !self.reported {
+                self.reported = true;
+                boundary.report(state.nb_executors.load(Ordering::SeqCst));
+            }
+        }
+        boundary.is_final && task >= boundary.first_skipped
+    }
+}
+
+impl CancellationBoundary {
+    /// Signal that an executor observed the cancellation, the boundary is final once all the
+    /// executors did.
+    fn report(&mut self, nb_executors: usize) {
+        self.nb_reported += 1;
+        self.is_final = self.nb_reported == nb_executors;
+    }
+}
+
+impl Drop for Cancellation {
+    fn drop(&mut self) {
+        // The executor issued all its tasks, it does not prevent the others from stopping anymore.
+        let state = &self.token.state;
+        let mut boundary = state.boundary.lock().unwrap();
+        if !self.reported && !boundary.is_final {
+            boundary.report(state.nb_executors.load(Ordering::SeqCst));
+        }
+    }
+}
+
+// ———————————————————————————————— Tracing ————————————————————————————————— //
+
+/// The execution of a task.
+struct TaskRecord {
+    task_id: usize,
+    executor_id: u32,
+    phase: usize,
+    start_ns: u64,
+    end_ns: u64,
+}
+
+/// The records of all the executors, timestamps are relative to `epoch`.
+struct TraceSink {
+    epoch: Instant,
+    records: Mutex<Vec<TaskRecord>>,
 }
 
+/// The records of an executor, flushed to the sink once the executor terminates.
+struct Tracer {
+    sink: Arc<TraceSink>,
+    records: Vec<TaskRecord>,
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        let mut records = self.sink.records.lock().unwrap();
+        records.append(&mut self.records);
+    }
+}
+
+// —————————————————————————————————— Go ———————————————————————————————————— //
+
 /// Start the computation on `nb_threads` threads.
 ///
 /// Each thread will execute the given function, but tasks declared with the `task!` macro will
-/// only be executed by the thread mapped to that task by the mapping function.
-pub fn go<'computation, Map, Args, T>(
+/// only be executed by the thread mapped to that task by the mapping function. The function can be
+/// a closure capturing its environment, it is shared by all the threads.
+pub fn go<'computation, Map, Args, T, Fun>(nb_threads: usize, map: Map, args: Args, fun: Fun)
+where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Clone + 'computation,
+    Fun: Fn(Runtime<'computation, T>, Args) + Sync,
+{
+    go_with(nb_threads, map, args, fun, |rt| rt);
+}
+
+/// Same as `go`, with the tasks attributed among the threads in a round robin fashion, so that the
+/// simplest programs do not need to define a mapping.
+pub fn go_round_robin<'computation, Args, Fun>(nb_threads: usize, args: Args, fun: Fun)
+where
+    Args: Send + Clone + 'computation,
+    Fun: Fn(Runtime<'computation>, Args) + Sync,
+{
+    go(
+        nb_threads,
+        get_round_robin_mapping(nb_threads as u32),
+        args,
+        fun,
+    );
+}
+
+/// Same as `go`, but the computation can be cancelled with `token`.
+///
+/// Once cancelled, the `task!` macro skips all the remaining tasks. The function executed by each
+/// thread can check `Runtime::is_cancelled` to stop early.
+///
+/// # Panics
+///
+/// Panics if the token has already been used for another computation.
+pub fn go_cancellable<'computation, Map, Args, T, Fun>(
     nb_threads: usize,
     map: Map,
     args: Args,
-    fun: fn(Runtime<'computation, T>, Args),
+    fun: Fun,
+    token: &CancellationToken,
 ) where
     Map: Mapping<T> + Clone + 'computation,
     Args: Send + Clone + 'computation,
+    Fun: Fn(Runtime<'computation, T>, Args) + Sync,
 {
+    let previous = token.state.nb_executors.swap(nb_threads, Ordering::SeqCst);
+    assert_eq!(previous, 0, "Cancellation tokens can not be reused");
+    go_with(nb_threads, map, args, fun, |rt| {
+        rt.with_cancellation(token.clone())
+    });
+}
+
+/// Same as `go`, but the computation stops once `should_stop` returns true, e.g. when an iterative
+/// solver converged. Returns true if the computation stopped because `should_stop` was met.
+///
+/// The threads share `state`, in which the executor computing the stopping criterion (e.g. the
+/// residual) stores it. Each executor evaluates `should_stop` before issuing a task, once it returns
+/// true the computation is cancelled: all the executors stop at the same task shortly after, the
+/// `task!` macro skipping the remaining tasks. The function executed by each thread can check
+/// `Runtime::is_cancelled` to exit its loop.
+pub fn go_until<'computation, Map, Args, T, State, Fun, Stop>(
+    nb_threads: usize,
+    map: Map,
+    args: Args,
+    state: &'computation State,
+    fun: Fun,
+    should_stop: Stop,
+) -> bool
+where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Clone + 'computation,
+    State: Sync,
+    Fun: Fn(Runtime<'computation, T>, &State, Args) + Sync,
+    Stop: Fn(&State) -> bool + Send + Sync + 'computation,
+{
+    let token = CancellationToken::new();
+    token.state.nb_executors.store(nb_threads, Ordering::SeqCst);
+    let should_stop = Arc::new(should_stop);
+    let worker = |rt, args| fun(rt, state, args);
+    go_with(nb_threads, map, args, worker, |rt| {
+        let should_stop = should_stop.clone();
+        rt.with_cancellation(token.clone())
+            .with_stop_condition(move || should_stop(state))
+    });
+    token.is_cancelled()
+}
+
+/// Same as `go`, but the execution of each task is recorded and written to `trace_out` once the
+/// computation is over.
+///
+/// The trace is a CSV file with a `task_id,executor_id,start_ns,end_ns,phase` line per task, where
+/// the timestamps are relative to the start of the computation and the phase is the one returned by
+/// `Runtime::phase` when the task was issued. Only the owner of a task records it,
+/// the synchronization is not affected.
+pub fn go_traced<'computation, Map, Args, T, Fun, W>(
+    nb_threads: usize,
+    map: Map,
+    args: Args,
+    fun: Fun,
+    mut trace_out: W,
+) -> io::Result<()>
+where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Clone + 'computation,
+    Fun: Fn(Runtime<'computation, T>, Args) + Sync,
+    W: Write,
+{
+    let sink = Arc::new(TraceSink {
+        epoch: Instant::now(),
+        records: Mutex::new(Vec::new()),
+    });
+    go_with(nb_threads, map, args, fun, |rt| {
+        rt.with_tracer(sink.clone())
+    });
+
+    let mut records = sink.records.lock().unwrap();
+    records.sort_by_key(|record| record.task_id);
+    writeln!(trace_out, "task_id,executor_id,start_ns,end_ns,phase")?;
+    for record in records.iter() {
+        writeln!(
+            trace_out,
+            "{},{},{},{},{}",
+            record.task_id, record.executor_id, record.start_ns, record.end_ns, record.phase
+        )?;
+    }
+    trace_out.flush()
+}
+
+/// Same as `go`, but each thread also owns an executor-local storage, created by calling
+/// `make_tls` with the thread ID before executing `fun`.
+///
+/// The storage can be used for per-thread scratch buffers or accumulators, it is dropped when the
+/// thread terminates.
+pub fn go_with_tls<'computation, Map, Args, T, Tls, MakeTls, Fun>(
+    nb_threads: usize,
+    map: Map,
+    args: Args,
+    make_tls: MakeTls,
+    fun: Fun,
+) where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Clone + 'computation,
+    MakeTls: Fn(u32) -> Tls + Sync,
+    Fun: Fn(Runtime<'computation, T>, &mut Tls, Args) + Sync,
+{
+    let worker = |rt: Runtime<'computation, T>, args| {
+        let mut tls = make_tls(rt.executor_id.thread_id);
+        fun(rt, &mut tls, args);
+    };
+    go_with(nb_threads, map, args, worker, |rt| rt);
+}
+
+/// Same as `go`, but the arguments are shared by all the threads instead of being cloned for each
+/// of them.
+///
+/// This avoids copying large read-only inputs, and allows arguments that are not `Clone`. Note
+/// that `Data` handles carry per-executor state and must still be cloned for each thread, they
+/// should be passed through `go` rather than shared.
+pub fn go_shared<'computation, Map, Args, T, Fun>(
+    nb_threads: usize,
+    map: Map,
+    args: Arc<Args>,
+    fun: Fun,
+) where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Sync + 'computation,
+    Fun: Fn(Runtime<'computation, T>, Arc<Args>) + Sync,
+{
+    go_with(nb_threads, map, args, fun, |rt| rt);
+}
+
+/// Start the computation, `configure` is called on the runtime of each thread before spawning it.
+fn go_with<'computation, Map, Args, T, Worker, Configure>(
+    nb_threads: usize,
+    map: Map,
+    args: Args,
+    worker: Worker,
+    configure: Configure,
+) where
+    Map: Mapping<T> + Clone + 'computation,
+    Args: Send + Clone + 'computation,
+    Worker: Fn(Runtime<'computation, T>, Args) + Sync,
+    Configure: Fn(Runtime<'computation, T>) -> Runtime<'computation, T>,
+{
+    let worker = &worker;
+    #[cfg(feature = "debug-checks")]
+    let task_counts = Arc::new(Mutex::new(Vec::with_capacity(nb_threads)));
+
     // The threads are scoped, they are guaranteed to terminate before `thread::scope` returns.
     thread::scope(|scope| {
         for thread_id in 0..nb_threads {
@@ -110,15 +784,37 @@ pub fn go<'computation, Map, Args, T>(
             // object.
             let map = map.clone();
             let args = args.clone();
-            let rt = Runtime::<'computation>::new(thread_id as u32, map);
+            let rt = configure(Runtime::<'computation>::new(thread_id as u32, map));
+            #[cfg(feature = "debug-checks")]
+            let rt = rt.with_task_counts(task_counts.clone());
 
             // Spawn the thread
             scope
                 .builder()
                 .name(format!("T{}", thread_id))
-                .spawn(move |_| fun(rt, args))
+                .spawn(move |_| worker(rt, args))
                 .unwrap();
         }
     })
     .expect("One of the workers panicked");
+
+    #[cfg(feature = "debug-checks")]
+    check_task_counts(&mut task_counts.lock().unwrap());
+}
+
+/// Panic if the executors did not issue the same number of tasks, which means that their task IDs
+/// diverged and that the synchronization is broken.
+#[cfg(feature = "debug-checks")]
+fn check_task_counts(task_counts: &mut [(u32, usize)]) {
+    task_counts.sort_unstable();
+    if task_counts.windows(2).any(|pair| pair[0].1 != pair[1].1) {
+        let task_counts: Vec<String> = task_counts
+            .iter()
+            .map(|(thread_id, count)| format!("T{}: {}", thread_id, count))
+            .collect();
+        panic!(
+            "Executors issued different numbers of tasks ({})",
+            task_counts.join(", ")
+        );
+    }
 }
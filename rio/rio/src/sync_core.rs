@@ -0,0 +1,204 @@
+//! # Synchronization Core
+//!
+//! The accounting behind `Data`: the local record of declared tasks, the shared record of executed
+//! tasks and the predicates telling whether a task is ready. This module only relies on `core` and
+//! `alloc` (the `no_std` test compiles it without `std`), the locking and parking of threads are
+//! provided through the `Lock` trait. `Data` plugs in a `std` mutex and conditional variable,
+//! environments without `std` (e.g. an embedded RTOS with a custom executor) can plug in their own
+//! primitives.
+
+use alloc::vec::Vec;
+use core::ops::DerefMut;
+
+// ——————————————————————————————— Local State —————————————————————————————— //
+
+/// The tasks declared on a data by a single executor.
+#[derive(Clone, Default)]
+pub struct LocalState {
+    pub(crate) last_registered_write: usize,
+    pub(crate) nb_reads_since_write: usize,
+    // A data is dirty if it has been written to since last time got access to it
+    pub(crate) dirty: bool,
+}
+
+impl LocalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a read task.
+    pub fn declare_read(&mut self) {
+        self.nb_reads_since_write += 1;
+    }
+
+    /// Record a write task.
+    pub fn declare_write(&mut self, task_id: usize) {
+        self.last_registered_write = task_id;
+        self.nb_reads_since_write = 0;
+        self.dirty = true;
+    }
+
+    /// Record a read task executed by this executor, the data is up to date afterward.
+    pub fn terminate_read(&mut self) {
+        self.declare_read();
+        self.dirty = false;
+    }
+
+    /// Record a write task executed by this executor, the data is up to date afterward.
+    pub fn terminate_write(&mut self, task_id: usize) {
+        self.declare_write(task_id);
+        self.dirty = false;
+    }
+
+    /// Whether a write has been declared since this executor last accessed the data.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// A read is ready once all the previous writes have been executed.
+    pub fn awaited_read(&self) -> Awaited {
+        Awaited::Read {
+            write: self.last_registered_write,
+        }
+    }
+
+    /// A write is ready once all the previous reads and writes have been executed.
+    pub fn awaited_write(&self) -> Awaited {
+        Awaited::Write {
+            write: self.last_registered_write,
+            reads: self.nb_reads_since_write,
+        }
+    }
+}
+
+// —————————————————————————————— Locked State —————————————————————————————— //
+
+/// The tasks executed on a data, shared by all the executors behind a `Lock`.
+#[derive(Default)]
+pub struct LockedState {
+    pub(crate) last_executed_write: usize,
+    pub(crate) nb_reads_since_write: usize,
+    // Whether a write task is being executed
+    pub(crate) writing: bool,
+    // The states awaited by the parked threads
+    pub(crate) waiters: Vec<Awaited>,
+    // Whether a write task panicked, leaving the data in an unknown state
+    pub(crate) poisoned: bool,
+}
+
+impl LockedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the start of a write task, which must be ready.
+    pub fn start_write(&mut self) {
+        self.writing = true;
+    }
+
+    /// Record the end of a read task.
+    pub fn execute_read(&mut self) {
+        self.nb_reads_since_write += 1;
+    }
+
+    /// Record the end of a write task.
+    pub fn execute_write(&mut self, task_id: usize) {
+        self.last_executed_write = task_id;
+        self.nb_reads_since_write = 0;
+        self.writing = false;
+    }
+
+    /// Whether a write task is being executed.
+    pub fn is_writing(&self) -> bool {
+        self.writing
+    }
+
+    /// Return the ID of the last write task executed, or 0 if there is none.
+    pub fn last_executed_write(&self) -> usize {
+        self.last_executed_write
+    }
+
+    /// Record that a write task panicked: the parked threads stop waiting, as the awaited state
+    /// might never be reached.
+    pub fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    /// Whether a write task panicked.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Whether at least one of the parked threads can make progress.
+    pub fn has_ready_waiter(&self) -> bool {
+        self.waiters
+            .iter()
+            .any(|awaited| awaited.is_satisfied(self))
+    }
+}
+
+/// A state of the data awaited by a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Awaited {
+    /// A read waits for the previous write to be executed.
+    Read { write: usize },
+    /// A write waits for the previous write and the reads that followed to be executed.
+    Write { write: usize, reads: usize },
+    /// Waits for a write, or any later one, to be executed.
+    AtLeast { write: usize },
+}
+
+impl Awaited {
+    pub fn is_satisfied(&self, state: &LockedState) -> bool {
+        match *self {
+            Awaited::Read { write } => state.last_executed_write == write,
+            Awaited::Write { write, reads } => {
+                state.last_executed_write == write && state.nb_reads_since_write == reads
+            }
+            Awaited::AtLeast { write } => state.last_executed_write >= write,
+        }
+    }
+}
+
+// ——————————————————————————————————— Lock ————————————————————————————————— //
+
+/// The primitives protecting a `LockedState` and parking the threads waiting on it.
+pub trait Lock {
+    type Guard<'a>: DerefMut<Target = LockedState>
+    where
+        Self: 'a;
+
+    /// Acquire the lock.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Release the lock and park the thread until notified, the lock is acquired again before
+    /// returning. Spurious wake ups are allowed.
+    fn wait<'a>(&'a self, guard: Self::Guard<'a>) -> Self::Guard<'a>;
+
+    /// Wake up all the parked threads.
+    fn notify_all(&self);
+}
+
+/// Park the thread until the `awaited` state is reached or the state is poisoned, `guard` must
+/// come from `lock`.
+pub fn wait_for<'a, L: Lock>(
+    lock: &'a L,
+    mut guard: L::Guard<'a>,
+    awaited: Awaited,
+) -> L::Guard<'a> {
+    guard.waiters.push(awaited);
+    while !awaited.is_satisfied(&guard) && !guard.poisoned {
+        guard = lock.wait(guard);
+    }
+    let idx = guard.waiters.iter().position(|w| *w == awaited).unwrap();
+    guard.waiters.swap_remove(idx);
+    guard
+}
+
+/// Wake up the parked threads, only if at least one of them can make progress or if the state is
+/// poisoned.
+pub fn wake_up<L: Lock>(lock: &L, state: &LockedState) {
+    if state.poisoned || state.has_ready_waiter() {
+        lock.notify_all();
+    }
+}
@@ -0,0 +1,180 @@
+//! # Task Graph
+//!
+//! An alternative to the `task!` macro, where the task graph is first built as data and then
+//! executed. Building the graph explicitly exposes its structure (e.g. for visualization or static
+//! analysis) and allows to replay it several times.
+//!
+//! The execution relies on the same machinery as the `task!` macro: each executor walks through
+//! the whole graph, executing the tasks it owns and declaring the others on its own copy of the
+//! data.
+
+use super::data::{Data, Ref, RefMut, TaskId};
+use super::runtime::{go, Mapping, Runtime};
+use std::mem;
+use std::sync::Arc;
+
+/// The function executed by a task, receiving the data it reads and the data it writes, in the
+/// order given when adding the task.
+pub type TaskFn<T> = fn(&[&T], &mut [&mut T]);
+
+/// A data identifier, only meaningful for the graph that returned it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataId(usize);
+
+/// A graph of tasks operating on data of type `T`.
+pub struct TaskGraph<T> {
+    tasks: Vec<Node<T>>,
+    data: Vec<Data<T>>,
+    // The accesses to each data, used to compute the dependencies
+    accesses: Vec<Accesses>,
+}
+
+struct Node<T> {
+    fun: TaskFn<T>,
+    reads: Vec<DataId>,
+    writes: Vec<DataId>,
+    dependencies: Vec<TaskId>,
+}
+
+#[derive(Default)]
+struct Accesses {
+    last_write: Option<TaskId>,
+    reads_since_write: Vec<TaskId>,
+}
+
+impl<T> TaskGraph<T> {
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            data: Vec::new(),
+            accesses: Vec::new(),
+        }
+    }
+
+    /// Add a data to the graph, it can then be accessed by tasks.
+    pub fn add_data(&mut self, data: T) -> DataId {
+        self.data.push(Data::new(data));
+        self.accesses.push(Accesses::default());
+        DataId(self.data.len() - 1)
+    }
+
+    /// Add a task reading and writing the given data, the task depends on all the previous tasks
+    /// accessing the same data in a conflicting way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a data does not belong to the graph or is accessed twice by the task.
+    pub fn add_task(&mut self, fun: TaskFn<T>, reads: &[DataId], writes: &[DataId]) -> TaskId {
+        let task_id = TaskId(self.tasks.len() + 1);
+        let mut accessed = vec![false; self.data.len()];
+        for data in reads.iter().chain(writes) {
+            assert!(data.0 < self.data.len(), "Unknown data {:?}", data);
+            assert!(!accessed[data.0], "{:?} is accessed twice by a task", data);
+            accessed[data.0] = true;
+        }
+
+        let mut dependencies = Vec::new();
+        for data in reads {
+            let accesses = &mut self.accesses[data.0];
+            dependencies.extend(accesses.last_write);
+            accesses.reads_since_write.push(task_id);
+        }
+        for data in writes {
+            let accesses = &mut self.accesses[data.0];
+            if accesses.reads_since_write.is_empty() {
+                dependencies.extend(accesses.last_write);
+            }
+            dependencies.append(&mut accesses.reads_since_write);
+            accesses.last_write = Some(task_id);
+        }
+        dependencies.sort_unstable_by_key(|task| task.0);
+        dependencies.dedup();
+
+        self.tasks.push(Node {
+            fun,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            dependencies,
+        });
+        task_id
+    }
+
+    /// Number of tasks in the graph.
+    pub fn nb_tasks(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Return the tasks that must be executed before `task`.
+    pub fn dependencies(&self, task: TaskId) -> &[TaskId] {
+        &self.tasks[task.0 - 1].dependencies
+    }
+
+    /// Return a mutable reference to a data, e.g. to read the results after an execution.
+    pub fn get_mut(&mut self, data: DataId) -> &mut T {
+        self.data[data.0]
+            .get_mut()
+            .expect("The data is still used by an execution")
+    }
+}
+
+impl<T> Default for TaskGraph<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ———————————————————————————————— Execute ————————————————————————————————— //
+
+type ReplayArgs<T> = (Arc<Vec<Node<T>>>, Vec<Data<T>>);
+
+/// Execute the graph on `nb_threads` threads, tasks are attributed to threads by the mapping
+/// function. The graph can be executed several times.
+pub fn execute<T, Map>(graph: &mut TaskGraph<T>, nb_threads: usize, map: Map)
+where
+    T: Send + Sync,
+    Map: Mapping + Clone,
+{
+    for data in &mut graph.data {
+        // Safety: the data are owned by the graph, which is mutably borrowed.
+        unsafe { data.reset() };
+    }
+    let tasks = Arc::new(mem::take(&mut graph.tasks));
+    go(nb_threads, map, (tasks.clone(), graph.data.clone()), replay);
+
+    // The executors are done, the graph is the only owner of the tasks again
+    graph.tasks = match Arc::try_unwrap(tasks) {
+        Ok(tasks) => tasks,
+        Err(_) => unreachable!("The tasks are still shared after the execution"),
+    };
+}
+
+fn replay<T>(mut rt: Runtime, args: ReplayArgs<T>) {
+    let (tasks, mut data) = args;
+    for task in tasks.iter() {
+        let (mut reads, mut writes) = borrow_data(&mut data, &task.reads, &task.writes);
+        // Safety: one task is issued per node, and it only accesses the data it reads and writes.
+        unsafe {
+            rt.issue_task(&mut reads, &mut writes, |task_id, reads, writes| {
+                let reads: Vec<Ref<T>> = reads.iter_mut().map(|d| d.get_read()).collect();
+                let mut writes: Vec<RefMut<T>> =
+                    writes.iter_mut().map(|d| d.get_write(task_id)).collect();
+                let reads: Vec<&T> = reads.iter().map(|r| &**r).collect();
+                let mut writes: Vec<&mut T> = writes.iter_mut().map(|w| &mut **w).collect();
+                (task.fun)(&reads, &mut writes);
+            });
+        }
+    }
+}
+
+/// Borrow the data accessed by a task, which are all distinct.
+fn borrow_data<'a, T>(
+    data: &'a mut [Data<T>],
+    reads: &[DataId],
+    writes: &[DataId],
+) -> (Vec<&'a mut Data<T>>, Vec<&'a mut Data<T>>) {
+    let mut slots: Vec<Option<&mut Data<T>>> = data.iter_mut().map(Some).collect();
+    let mut take = |id: &DataId| slots[id.0].take().unwrap();
+    let reads = reads.iter().map(&mut take).collect();
+    let writes = writes.iter().map(&mut take).collect();
+    (reads, writes)
+}
@@ -5,31 +5,92 @@ macro_rules! task {
     // —————————————————————————————————————————————————————————————————————————— //
 
     ($rt:ident, $fun:ident) => {
-        task!{handle_task $rt, $fun, [], []}
+        task!{handle_task $rt, $fun, [], [], []}
     };
 
     ($rt:ident, $fun:ident, R: $($read_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [$($read_data),+], []}
+        task!{handle_task $rt, $fun, [$($read_data),+], [], []}
     };
 
     ($rt:ident, $fun:ident, RW: $($write_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [], [$($write_data),+]}
+        task!{handle_task $rt, $fun, [], [$($write_data),+], []}
     };
 
     ($rt:ident, $fun:ident, R: $($read_data:ident),+; RW: $($write_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+]}
+        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+], []}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr $(;)?) => {
+        task!{handle_task $rt, $fun, [], [], [], $args}
     };
 
     ($rt:ident, $fun:ident, map: $args:expr; R: $($read_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [$($read_data),+], [], $args}
+        task!{handle_task $rt, $fun, [$($read_data),+], [], [], $args}
     };
 
     ($rt:ident, $fun:ident, map: $args:expr; RW: $($write_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [], [$($write_data),+], $args}
+        task!{handle_task $rt, $fun, [], [$($write_data),+], [], $args}
     };
 
     ($rt:ident, $fun:ident, map: $args:expr; R: $($read_data:ident),+; RW: $($write_data:ident),+ $(;)?) => {
-        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+], $args}
+        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+], [], $args}
+    };
+
+    // Move the values of `OnceData` cells into the task, the function takes them by value after the
+    // data it reads and writes
+    ($rt:ident, $fun:ident, own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [], [], [$($own_data),+]}
+    };
+
+    ($rt:ident, $fun:ident, R: $($read_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [$($read_data),+], [], [$($own_data),+]}
+    };
+
+    ($rt:ident, $fun:ident, RW: $($write_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [], [$($write_data),+], [$($own_data),+]}
+    };
+
+    ($rt:ident, $fun:ident, R: $($read_data:ident),+; RW: $($write_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+], [$($own_data),+]}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [], [], [$($own_data),+], $args}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr; R: $($read_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [$($read_data),+], [], [$($own_data),+], $args}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr; RW: $($write_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [], [$($write_data),+], [$($own_data),+], $args}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr; R: $($read_data:ident),+; RW: $($write_data:ident),+; own: $($own_data:ident),+ $(;)?) => {
+        task!{handle_task $rt, $fun, [$($read_data),+], [$($write_data),+], [$($own_data),+], $args}
+    };
+
+    // Name the task, its ownership decision is logged at trace level with the `log` feature
+    ($rt:ident, $fun:ident, debug_name: $name:expr $(;)?) => {
+        $rt.set_debug_name($name);
+        task!{$rt, $fun}
+    };
+
+    ($rt:ident, $fun:ident, debug_name: $name:expr; $($rest:tt)+) => {
+        $rt.set_debug_name($name);
+        task!{$rt, $fun, $($rest)+}
+    };
+
+    // Read `$src` and write `$dst`, which must be two distinct data, e.g. the two buffers of a
+    // stencil swapping their roles at each iteration.
+    ($rt:ident, $fun:ident, RW_from: $src:ident -> $dst:ident $(;)?) => {
+        task!{check_distinct $src, $dst}
+        task!{handle_task $rt, $fun, [$src], [$dst], []}
+    };
+
+    ($rt:ident, $fun:ident, map: $args:expr; RW_from: $src:ident -> $dst:ident $(;)?) => {
+        task!{check_distinct $src, $dst}
+        task!{handle_task $rt, $fun, [$src], [$dst], [], $args}
     };
 
     // —————————————————————————— Private Main Handler —————————————————————————— //
@@ -37,47 +98,79 @@ macro_rules! task {
     // at once while still exposing a nice interface to the user.                 //
     // —————————————————————————————————————————————————————————————————————————— //
 
-    (handle_task $rt:ident, $fun:ident, [$($read_data:ident),*], [$($write_data:ident),*] $(,)? $($args:expr)?) => {
-        unsafe {
-            let (_task_id, ownership) = task!{get_task_id $rt, $($args)?};
-            match ownership {
-                $crate::TaskOwnership::Owner => {
-                    {
-                        // Get the data
-                        task!{get_data_read  $($read_data),*}
-                        task!{get_data_write _task_id, $($write_data),*}
-
-                        // Perform the task
-                        task!(call_fun $fun, [$($read_data),*], [$($write_data),*])
+    (handle_task $rt:ident, $fun:ident, [$($read_data:ident),*], [$($write_data:ident),*], [$($own_data:ident),*] $(,)? $($args:expr)?) => {
+        {
+            let (_task_id, owner) = task!{get_task_id $rt, $($args)?};
+            // Tasks without data do not call any unsafe function
+            #[allow(unused_unsafe)]
+            unsafe {
+                match owner {
+                    Some(owner) if owner == $rt.executor_id() => {
+                        {
+                            // Get the data
+                            task!{get_data_read  $($read_data),*}
+                            task!{get_data_write _task_id, $($write_data),*}
+                            task!{get_data_own _task_id, $($own_data),*}
+
+                            // Perform the task
+                            let start = $rt.trace_start();
+                            task!(call_fun $fun, [$($read_data),*], [$($write_data),*], [$($own_data),*]);
+                            $rt.trace_end(_task_id, start);
+                        }
                     }
+                    Some(_) => {
+                        if let Some(max_in_flight) = $rt.max_in_flight() {
+                            task!{wait_in_flight _task_id, max_in_flight, $($read_data,)* $($write_data,)* $($own_data),*}
+                        }
+                        task!{register_task_read $($read_data),*}
+                        task!{register_task_write _task_id, $($write_data,)* $($own_data),*}
+                        if $rt.yield_if_not_owner() {
+                            std::thread::yield_now();
+                        }
+                    }
+                    // The computation has been cancelled
+                    None => {}
                 }
-                $crate::TaskOwnership::NotOwner => {
-                    task!{register_task_read $($read_data),*}
-                    task!{register_task_write _task_id, $($write_data),*}
-                }
-            }
+            };
+        }
+    };
+
+    // ———————————————————————————— Check Distinct —————————————————————————————— //
+
+    (check_distinct $src:ident, $dst:ident) => {
+        {
+            // Fails to compile if the same identifier is used twice, as the variants collide
+            #[allow(non_camel_case_types, dead_code)]
+            enum DistinctData { $src, $dst }
+            assert!(
+                !$crate::Data::ptr_eq(&$src, &$dst),
+                "RW_from: `{}` and `{}` are the same data",
+                stringify!($src),
+                stringify!($dst),
+            );
         }
     };
 
     // —————————————————————————————— Get task_id ——————————————————————————————— //
 
-    // Using custom mapping arguments
+    // Using custom mapping arguments, evaluated outside of the unsafe block as they can be any
+    // expression
     (get_task_id $rt:ident, $args:expr) => {
-        $rt.next_task_args($args)
+        {
+            let args = $args;
+            unsafe { $rt.next_task_args(args) }
+        }
     };
 
     // Default case: using TaskID (`usize`) as mapping argument
     (get_task_id $rt:ident,) => {
-        $rt.next_task()
+        unsafe { $rt.next_task() }
     };
 
     // ————————————————————————————— Call Function —————————————————————————————— //
 
-    (call_fun $fun:ident, [$($read_args:ident),+], [$($write_args:ident),*]) => {
-        $fun($(&$read_args),+, $(&mut $write_args),*);
-    };
-    (call_fun $fun:ident, [], [$($write_args: ident),*]) => {
-        $fun($(&mut $write_args),*);
+    (call_fun $fun:ident, [$($read_args:ident),*], [$($write_args:ident),*], [$($own_args:ident),*]) => {
+        $fun($(&$read_args,)* $(&mut $write_args,)* $($own_args,)*);
     };
 
     // ———————————————————————————————— Get Data ———————————————————————————————— //
@@ -100,6 +193,20 @@ macro_rules! task {
         task!{get_data_write $task_id, $($datas),+}
     };
 
+    (get_data_own $task_id:ident $(,)?) => {};
+    (get_data_own $task_id:ident, $($data:ident),+) => {
+        $(let $data = $data.take($task_id);)+
+    };
+
+    // ————————————————————————————— Flow Control ——————————————————————————————— //
+
+    (wait_in_flight $task_id:ident, $max:ident $(,)?) => {
+        let _ = $max;
+    };
+    (wait_in_flight $task_id:ident, $max:ident, $($data:ident),+ $(,)?) => {
+        $($data.wait_in_flight($task_id, $max);)+
+    };
+
     // —————————————————————————————— Register Task ————————————————————————————— //
 
     (register_task_read ) => {};
@@ -112,10 +219,10 @@ macro_rules! task {
     };
 
     (register_task_write $task_id:ident $(,)?) => {};
-    (register_task_write $task_id:ident, $data:ident) => {
+    (register_task_write $task_id:ident, $data:ident $(,)?) => {
         $data.declare_write($task_id);
     };
-    (register_task_write $task_id:ident, $data:ident, $($datas:ident),+) => {
+    (register_task_write $task_id:ident, $data:ident, $($datas:ident),+ $(,)?) => {
         task!{register_task_write $task_id, $data}
         task!{register_task_write $task_id, $($datas),+}
     };
@@ -0,0 +1,85 @@
+//! # Tile Matrix
+//!
+//! Tiled algorithms (matrix multiplication, LU factorization...) express dependencies between the
+//! tiles of a matrix, where each tile is a `Data`. The task program needs mutable references to
+//! several tiles at once in order to declare tasks, the `TileMatrix` hands out such references
+//! without requiring each program to juggle with raw pointers.
+
+use super::data::Data;
+use std::cell::UnsafeCell;
+
+/// A matrix of `rows` x `cols` tiles, stored in row-major order.
+pub struct TileMatrix<T> {
+    tiles: Box<[UnsafeCell<Data<T>>]>,
+    rows: usize,
+    cols: usize,
+}
+
+impl<T> TileMatrix<T> {
+    /// Create a matrix, initializing the tile (i, j) with `init(i, j)`.
+    pub fn from_fn<F>(rows: usize, cols: usize, mut init: F) -> Self
+    where
+        F: FnMut(usize, usize) -> T,
+    {
+        let tiles = (0..rows * cols)
+            .map(|idx| UnsafeCell::new(Data::new(init(idx / cols, idx % cols))))
+            .collect();
+        Self { tiles, rows, cols }
+    }
+
+    /// Number of rows of tiles.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns of tiles.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Return a mutable reference to tile (i, j).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tile is out of the matrix, so that two distinct pairs never alias.
+    ///
+    /// # Safety
+    ///
+    /// It is up to the caller to ensure that no two references to the same tile are alive at the
+    /// same time, references to distinct tiles can be used together.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn tile(&self, i: usize, j: usize) -> &mut Data<T> {
+        assert!(
+            i < self.rows && j < self.cols,
+            "Tile ({}, {}) is out of a {}x{} matrix",
+            i,
+            j,
+            self.rows,
+            self.cols
+        );
+        &mut *self.tiles[i * self.cols + j].get()
+    }
+}
+
+impl<T: Default> TileMatrix<T> {
+    /// Create a matrix of default tiles.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self::from_fn(rows, cols, |_, _| T::default())
+    }
+}
+
+impl<T> Clone for TileMatrix<T> {
+    fn clone(&self) -> Self {
+        // Safety: the references are only alive during the call to `clone`.
+        let tiles = self
+            .tiles
+            .iter()
+            .map(|tile| UnsafeCell::new(unsafe { &*tile.get() }.clone()))
+            .collect();
+        Self {
+            tiles,
+            rows: self.rows,
+            cols: self.cols,
+        }
+    }
+}
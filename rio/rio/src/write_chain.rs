@@ -0,0 +1,51 @@
+//! # Write Chain
+//!
+//! Reductions and running sums update a single data through a chain of write tasks, each task
+//! depending on the previous one. A `WriteChain` issues these tasks directly, relying on the same
+//! machinery as the `task!` macro.
+
+use super::data::Data;
+use super::runtime::Runtime;
+
+/// A data updated by a chain of write tasks, applied in the order they are issued.
+///
+/// As for `Data`, each executor works on its own clone of the chain and must issue the same
+/// updates as the others.
+#[derive(Clone)]
+pub struct WriteChain<T> {
+    data: Data<T>,
+}
+
+impl<T> WriteChain<T> {
+    pub fn new(value: T) -> Self {
+        Self::from_data(Data::new(value))
+    }
+
+    /// Chain the updates of an existing data.
+    pub fn from_data(data: Data<T>) -> Self {
+        Self { data }
+    }
+
+    /// Issue a write task applying `update` to the value, the owner of the task executes it once
+    /// the previous updates are done.
+    pub fn update<F>(&mut self, rt: &mut Runtime, update: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        // Safety: one task is issued per update, and it only writes the data.
+        unsafe {
+            rt.issue_task(&mut [], &mut [&mut self.data], |task_id, _, writes| {
+                update(&mut writes[0].get_write(task_id))
+            });
+        }
+    }
+
+    /// The underlying data, e.g. to read the value from a `task!`.
+    pub fn data(&mut self) -> &mut Data<T> {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Data<T> {
+        self.data
+    }
+}
@@ -0,0 +1,7 @@
+//! Programs that must be rejected at compile time.
+
+#[test]
+fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile_fail/*.rs");
+}
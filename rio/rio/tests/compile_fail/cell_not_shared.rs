@@ -0,0 +1,11 @@
+// A `Cell` is not `Sync`, concurrent read tasks would race on it.
+
+use rio::Data;
+use std::cell::Cell;
+
+fn assert_send<T: Send>(_: T) {}
+
+fn main() {
+    let data = Data::new(Cell::new(0));
+    assert_send(data.clone());
+}
@@ -0,0 +1,10 @@
+// An `Rc` is neither `Send` nor `Sync`, its reference count would be updated from several threads.
+
+use rio::{get_round_robin_mapping, go, Data, Runtime};
+use std::rc::Rc;
+
+fn worker(_: Runtime, _: Data<Rc<i32>>) {}
+
+fn main() {
+    go(2, get_round_robin_mapping(2), Data::new(Rc::new(0)), worker);
+}
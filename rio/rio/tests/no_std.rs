@@ -0,0 +1,28 @@
+//! The synchronization core only relies on `core` and `alloc`: the module is compiled here in a
+//! crate without `std`, so that any use of `std` is rejected.
+
+#![no_std]
+// Not every item of the module is used by the test
+#![allow(dead_code)]
+
+extern crate alloc;
+// Required by the test harness, under another name so that `std` paths do not resolve
+extern crate std as host_std;
+
+#[path = "../src/sync_core.rs"]
+mod sync_core;
+
+use sync_core::{Awaited, LocalState, LockedState};
+
+#[test]
+fn sync_core_without_std() {
+    let mut local = LocalState::new();
+    let mut locked = LockedState::new();
+    local.declare_write(1);
+    let awaited = local.awaited_read();
+    assert_eq!(awaited, Awaited::Read { write: 1 });
+    assert!(!awaited.is_satisfied(&locked));
+    locked.start_write();
+    locked.execute_write(1);
+    assert!(awaited.is_satisfied(&locked));
+}